@@ -0,0 +1,114 @@
+use termion::{color, cursor};
+
+use crate::position::Position;
+
+// One character on screen, no color means the terminal default
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub char: char,
+    pub bg: Option<u8>,
+    pub fg: Option<u8>,
+}
+
+const BLANK: Cell = Cell { char: ' ', bg: None, fg: None };
+
+// Grid of cells with a pen that remembers the cursor and colors like a terminal does
+#[derive(Clone)]
+pub struct Buffer {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: (u16, u16),
+    bg: Option<u8>,
+    fg: Option<u8>,
+}
+
+impl Buffer {
+    pub fn new(size: Position) -> Self {
+        Buffer {
+            width: size.get_x(),
+            height: size.get_y(),
+            cells: vec![BLANK; size.get_x() as usize * size.get_y() as usize],
+            cursor: (1, 1),
+            bg: None,
+            fg: None,
+        }
+    }
+
+    pub fn get_size(&self) -> Position {
+        Position::new(self.width, self.height)
+    }
+
+    pub fn get(&self, x: u16, y: u16) -> Option<Cell> {
+        self.get_index(x, y).map(|index| self.cells[index])
+    }
+
+    fn get_index(&self, x: u16, y: u16) -> Option<usize> {
+        if x == 0 || y == 0 || x > self.width || y > self.height { return None; }
+        Some((y - 1) as usize * self.width as usize + (x - 1) as usize)
+    }
+
+    pub fn go_to(&mut self, x: u16, y: u16) {
+        self.cursor = (x, y);
+    }
+
+    pub fn set_bg(&mut self, color: Option<u8>) {
+        self.bg = color;
+    }
+
+    pub fn set_fg(&mut self, color: Option<u8>) {
+        self.fg = color;
+    }
+
+    // Text past the right edge is cut off instead of wrapping
+    pub fn write(&mut self, text: &str) {
+        for char in text.chars() {
+            let char = if char.is_control() { ' ' } else { char };
+            let (x, y) = self.cursor;
+            if let Some(index) = self.get_index(x, y) {
+                self.cells[index] = Cell { char, bg: self.bg, fg: self.fg };
+            }
+            self.cursor.0 = x.saturating_add(1);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = BLANK;
+        }
+    }
+
+    // Escape codes that turn old into this buffer, only touching cells that changed
+    pub fn diff(&self, old: &Buffer) -> String {
+        let mut output = String::new();
+        let mut position = None;
+        let mut bg = None;
+        let mut fg = None;
+        for y in 1..=self.height {
+            for x in 1..=self.width {
+                let cell = self.cells[self.get_index(x, y).unwrap()];
+                if old.get(x, y) == Some(cell) { continue; }
+                if position != Some((x, y)) {
+                    output += &cursor::Goto(x, y).to_string();
+                }
+                if bg != Some(cell.bg) {
+                    output += &match cell.bg {
+                        Some(bg) => color::Bg(color::AnsiValue(bg)).to_string(),
+                        None => color::Bg(color::Reset).to_string(),
+                    };
+                }
+                if fg != Some(cell.fg) {
+                    output += &match cell.fg {
+                        Some(fg) => color::Fg(color::AnsiValue(fg)).to_string(),
+                        None => color::Fg(color::Reset).to_string(),
+                    };
+                }
+                output.push(cell.char);
+                position = Some((x + 1, y));
+                bg = Some(cell.bg);
+                fg = Some(cell.fg);
+            }
+        }
+        output
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use chrono::{Date, Datelike, Local};
+
+use crate::calendar::Calendar;
+
+// Calendars that went off screen, kept around so moving back and forth in time
+// doesn't have to lay out the same months again
+pub struct MonthCache {
+    months: HashMap<(i32, u32), Calendar>,
+}
+
+impl MonthCache {
+    pub fn new() -> Self {
+        MonthCache { months: HashMap::new() }
+    }
+
+    pub fn take(&mut self, date: Date<Local>) -> Option<Calendar> {
+        self.months.remove(&(date.year(), date.month()))
+    }
+
+    pub fn put(&mut self, calendar: Calendar) {
+        let date = calendar.get_start_date();
+        self.months.insert((date.year(), date.month()), calendar);
+    }
+
+    // Layouts depend on the config and the terminal size so they all go when those change
+    pub fn clear(&mut self) {
+        self.months.clear();
+    }
+
+    // Only keep the months closest to date
+    pub fn trim(&mut self, date: Date<Local>, limit: usize) {
+        if self.months.len() <= limit { return; }
+        let center = get_month_number(date.year(), date.month());
+        let mut keys: Vec<_> = self.months.keys().copied().collect();
+        keys.sort_by_key(|(year, month)| (get_month_number(*year, *month) - center).abs());
+        for key in keys.into_iter().skip(limit) {
+            self.months.remove(&key);
+        }
+    }
+}
+
+fn get_month_number(year: i32, month: u32) -> i64 {
+    year as i64 * 12 + month as i64
+}
@@ -1,36 +1,51 @@
-use chrono::{Date, Datelike, Local, Weekday};
+use chrono::{Date, Local, NaiveDate, Weekday};
 use termion::color::AnsiValue;
 
-use crate::{config::Config, position::{Direction, Position}, terminal::Formatter, tui::{Button, ButtonType, TextBox, Widget}};
-
-static mut WEEKDAYS: Option<TextBox> = None; // Use this to hold weekday textbox
+use crate::{config::Config, month::{self, Month}, notes::Notes, position::{Direction, Position}, terminal::Formatter, tui::{self, Button, ButtonType, TextBox, Widget}};
 
 #[derive(Clone)]
 pub struct Calendar {
     start_date: Date<Local>,
+    // The weeks the days are laid out in, buttons only know where they ended up on screen
+    month: Option<Month>,
     start: Position,
     end: Position,
     pub buttons: Vec<Button>,
     pub cursor: usize,
-    bg_color: AnsiValue
+    bg_color: AnsiValue,
+    selected: bool,
+    range: Option<(NaiveDate, NaiveDate)>,
+    row_height: u16,
+    weekdays: TextBox,
+    // Part of the screen the calendar is seen through when the months scroll
+    scroll: u16,
+    last_row: u16,
 }
 
 impl Calendar {
-    pub fn new(start_date: Date<Local>, start: Position, config: &Config) -> Self {
+    pub fn new(start_date: Date<Local>, start: Position, compact: bool, config: &Config) -> Self {
+        // Weeks are every other row unless the calendar is compact
+        let row_height = if compact { 1 } else { 2 };
         let mut calendar = Calendar {
             start_date,
+            month: Month::containing(start_date.naive_local(), config.week_start),
             start,
-            end: Position::new(start.get_x() + 21, start.get_y() + 12),
+            end: Position::new(start.get_x() + 21, start.get_y() + 2 + 5 * row_height),
             buttons: Vec::new(),
             cursor: 0,
             bg_color: config.calendar_bg_color,
+            selected: false,
+            range: None,
+            row_height,
+            // Built from the config every time so a new week start shows up right away
+            weekdays: TextBox::new(
+                get_weekdays(config.week_start),
+                Position::new(start.get_x(), start.get_y() + 1),
+                config.weekday_bg_color,
+            ),
+            scroll: 0,
+            last_row: u16::MAX,
         };
-        unsafe {
-            if let None = WEEKDAYS {
-                let weekdays = TextBox::new(get_weekdays(start_date), Position::new_center(), config.weekday_bg_color);
-                WEEKDAYS = Some(weekdays);
-            }
-        }
         calendar.setup(config);
         calendar
     }
@@ -38,19 +53,25 @@ impl Calendar {
     pub fn dummy() -> Self {
         Calendar {
             start_date: Local::today(),
+            month: Month::containing(Local::today().naive_local(), Weekday::Sun),
             start: Position::new_origin(),
             end: Position::new_origin(),
             buttons: Vec::new(),
             cursor: 0,
             bg_color: AnsiValue(0),
+            selected: false,
+            range: None,
+            row_height: 2,
+            weekdays: TextBox::new(String::new(), Position::new_origin(), AnsiValue(0)),
+            scroll: 0,
+            last_row: u16::MAX,
         }
     }
 
     fn setup(&mut self, config: &Config) {
-        let mut  date = self.start_date.clone();
         // Make title bar button
         let button = Button {
-            button_data: ButtonType::TextButton(date.format("%B %Y").to_string()),
+            button_data: ButtonType::TextButton(self.start_date.format("%B %Y").to_string()),
             start_position: self.start,
             end_position: Position::new(self.start.get_x() + 21, self.start.get_y()),
             bg_color: config.text_button_bg_color,
@@ -58,102 +79,198 @@ impl Calendar {
             internal_text: String::new(),
         };
         self.buttons.push(button);
-        let mut position = self.start.clone();
-        position.set(
-            position.get_x() + 1 + 3 * (date.weekday().number_from_sunday() as u16 - 1),
-            position.get_y() + 2,
-        );
-
-        // Count days in a month
-        let days = {
-            let mut date = date.clone();
-            let mut days = 0;
-            while self.start_date.month() == date.month() {
-                days += 1;
-                date = date.succ();
+
+        // Add days in a month, the days of other months filling up the weeks are left blank
+        let weeks = self.month.map_or_else(Vec::new, |month| month.get_weeks());
+        for (row, week) in weeks.iter().enumerate() {
+            for (column, day) in week.days.iter().enumerate() {
+                if !day.in_month { continue; }
+                let date = tui::to_local(day.date, self.start_date);
+                let position = self.get_cell_position(row, column);
+                let button = Button {
+                    button_data: ButtonType::CalanderDate(date),
+                    start_position: position,
+                    end_position: Position::new(position.get_x() + 1, position.get_y()),
+                    bg_color: config.date_bg_color,
+                    fg_color: config.date_num_color,
+                    internal_text: String::new(),
+                };
+                self.buttons.push(button);
             }
-            days
-        };
+        }
+    }
 
-        // Add days in a month
-        for _ in 1..=days {
-            let button = Button {
-                button_data: ButtonType::CalanderDate(date),
-                start_position: position,
-                end_position: Position::new(position.get_x() + 1, position.get_y()),
-                bg_color: config.date_bg_color,
-                fg_color: config.date_num_color,
-                internal_text: String::new(),
-            };
-            self.buttons.push(button);
-            date = date.succ();
-            let days_from_max = date.weekday().num_days_from_sunday() as u16;
-            position.set(
-                self.start.get_x() + 1 + 3 * days_from_max,
-                position.get_y() + if days_from_max == 0 { 2 } else { 0 }
-            );
+    // Where the day in a row and column of the month is drawn
+    fn get_cell_position(&self, row: usize, column: usize) -> Position {
+        Position::new(
+            self.start.get_x() + 1 + 3 * column as u16,
+            self.start.get_y() + 2 + row as u16 * self.row_height,
+        )
+    }
+
+    // Button of the day in a row and column, None for the blanks of other months
+    fn get_button(&self, row: usize, column: usize) -> Option<usize> {
+        let day = *self.month?.get_weeks().get(row)?.days.get(column)?;
+        if !day.in_month { return None; }
+        self.buttons.iter().position(|button| {
+            matches!(button.button_data, ButtonType::CalanderDate(date) if date.naive_local() == day.date)
+        })
+    }
+
+    // Row and column of a date button, None for the title
+    fn get_cell(&self, index: usize) -> Option<(usize, usize)> {
+        match self.buttons.get(index)?.button_data {
+            ButtonType::CalanderDate(date) => self.month?.find(date.naive_local()),
+            ButtonType::TextButton(_) => None,
         }
     }
 
+    // Shift everything so the calendar starts at position instead of laying it out again
+    pub fn move_to(&mut self, start: Position) {
+        let x = start.get_x() as i32 - self.start.get_x() as i32;
+        let y = start.get_y() as i32 - self.start.get_y() as i32;
+        if x == 0 && y == 0 { return; }
+        self.start = start;
+        self.end = self.end.offset(x, y);
+        for button in self.buttons.iter_mut() {
+            button.start_position = button.start_position.offset(x, y);
+            button.end_position = button.end_position.offset(x, y);
+        }
+        self.weekdays.set_position(Position::new(start.get_x(), start.get_y() + 1));
+    }
+
+    // Positions stay where the layout put them, only what gets drawn is moved up by scroll
+    pub fn set_view(&mut self, scroll: u16, last_row: u16) {
+        self.scroll = scroll;
+        self.last_row = last_row;
+    }
+
+    fn view(&self, format: Formatter) -> Formatter {
+        format.scroll(self.scroll, self.last_row)
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+
+    // Drop the cursor so a reused calendar looks like a fresh one after set_range
+    pub fn clear_selection(&mut self) {
+        self.cursor = 0;
+        self.selected = false;
+    }
+
     pub fn move_cursor(&mut self, config: &Config, direction: Direction) -> Formatter {
-        let index_to = match direction {
-            Direction::Up => 
-                if self.cursor <= 7 { 0 } else { self.cursor - 7 },
-            Direction::Down => 
-                if self.cursor + 7 >= self.buttons.len() { self.buttons.len() - 1 } else { self.cursor + 7 },
-            Direction::Left => 
-                if self.cursor == 0 { 0 } else { self.cursor - 1 },
-            Direction::Right =>
-                if self.cursor + 1 >= self.buttons.len() { self.buttons.len() - 1 } else { self.cursor + 1 },
+        let last = self.buttons.len().saturating_sub(1);
+        let index_to = 
+        match (self.get_cell(self.cursor), self.get_neighbour(direction)) {
+            (Some(_), Some(index)) => index,
+            // Past the first day is the title, past the last day there is nothing more
+            (Some(_), None) => match direction {
+                Direction::Up | Direction::Left => 0,
+                Direction::Down | Direction::Right => last,
+            },
+            // From the title down or right is the first day
+            (None, _) => match direction {
+                Direction::Up | Direction::Left => self.cursor,
+                Direction::Down | Direction::Right => 1.min(last),
+            },
         };
         self.select_button(config, index_to)
     }
 
+    // The day next to the cursor on the grid, None when that is outside the month
+    pub fn get_neighbour(&self, direction: Direction) -> Option<usize> {
+        let (row, column) = self.get_cell(self.cursor)?;
+        let (row, column) = match direction {
+            Direction::Up => (row.checked_sub(1)?, column),
+            Direction::Down => (row + 1, column),
+            Direction::Left => if column > 0 { (row, column - 1) } else { (row.checked_sub(1)?, 6) },
+            Direction::Right => if column < 6 { (row, column + 1) } else { (row + 1, 0) },
+        };
+        self.get_button(row, column)
+    }
+
     pub fn select_button(&mut self, config: &Config, index_to: usize) -> Formatter {
         let mut format = Formatter::new();
         if index_to >= self.buttons.len() { return format; }
         if self.cursor != index_to { format += &self.unselect_button(config); }
-        let button = self.buttons.get_mut(index_to);
-        match button {
-            Some(button) => {
-                button.bg_color = match button.button_data {
-                    ButtonType::TextButton(_) => config.select_bg_text_button_color,
-                    ButtonType::CalanderDate(_) => config.select_bg_date_color,
-                };
-                format += &button.draw_format();
-                self.cursor = index_to;
-            }
-            None => (),
+        if let Some(button) = self.buttons.get_mut(index_to) {
+            button.bg_color = match button.button_data {
+                ButtonType::TextButton(_) => config.select_bg_text_button_color,
+                ButtonType::CalanderDate(_) => config.select_bg_date_color,
+            };
+            let button = button.draw_format();
+            format += &self.view(button);
+            self.cursor = index_to;
+            self.selected = true;
         }
         format
     }
 
     pub fn unselect_button(&mut self, config: &Config) -> Formatter {
         let mut format = Formatter::new();
-        match self.buttons.get_mut(self.cursor) {
-            Some(button) => {
-                button.bg_color = match button.button_data {
-                    ButtonType::TextButton(_) => config.text_button_bg_color,
-                    ButtonType::CalanderDate(_) => config.date_bg_color,
-                };
+        let color = self.get_bg_color(config, self.cursor);
+        if let Some(button) = self.buttons.get_mut(self.cursor) {
+            button.bg_color = color;
+            format += &button.draw_format();
+        }
+        self.selected = false;
+        self.view(format)
+    }
+
+    // Background of an unselected button, date buttons inside the range are highlighted
+    fn get_bg_color(&self, config: &Config, index: usize) -> AnsiValue {
+        match self.buttons.get(index).map(|button| &button.button_data) {
+            Some(ButtonType::CalanderDate(date)) => match self.range {
+                Some((start, end)) if start <= date.naive_local() && date.naive_local() <= end =>
+                    config.range_bg_color,
+                _ => config.date_bg_color,
+            },
+            _ => config.text_button_bg_color,
+        }
+    }
+
+    pub fn set_range(&mut self, config: &Config, range: Option<(NaiveDate, NaiveDate)>) -> Formatter {
+        let mut format = Formatter::new();
+        self.range = range;
+        for index in 0..self.buttons.len() {
+            if self.selected && index == self.cursor { continue; }
+            let color = self.get_bg_color(config, index);
+            let button = &mut self.buttons[index];
+            if button.bg_color.0 != color.0 {
+                button.bg_color = color;
                 format += &button.draw_format();
             }
-            None => (),
         }
-        format
+        self.view(format)
     }
 
-    pub fn get_start_date(&self) -> Date<Local> {
-        self.start_date
+    pub fn find_button(&self, position: Position) -> Option<usize> {
+        if !self.is_hovered(position) { return None; }
+        self.buttons.iter().position(|button| button.is_hovered(position))
     }
 
-    pub fn get_text_button(&self) -> Option<&Button> {
-        for button in self.buttons.iter() {
-            if let ButtonType::TextButton(_) = button.button_data {
-                return Some(button);
+    pub fn load_notes(&mut self, notes: &Notes) {
+        let mut text = String::new();
+        for button in self.buttons.iter_mut() {
+            if let ButtonType::CalanderDate(date) = button.button_data {
+                button.internal_text = notes.get(date.naive_local()).to_string();
+                text += &button.internal_text;
             }
         }
-        None
+        // The title button holds every note of the month as an overview
+        if let Some(button) = self.get_mut_text_button() {
+            button.internal_text = text;
+        }
+    }
+
+    pub fn find_date(&self, date: Date<Local>) -> Option<usize> {
+        let (row, column) = self.month?.find(date.naive_local())?;
+        self.get_button(row, column)
+    }
+
+    pub fn get_start_date(&self) -> Date<Local> {
+        self.start_date
     }
 
     pub fn get_mut_text_button(&mut self) -> Option<&mut Button> {
@@ -172,19 +289,7 @@ impl Widget for Calendar {
         for button in self.buttons.iter_mut() {
             format += &button.draw_format();
         }
-        unsafe {
-            match &mut WEEKDAYS {
-                Some(textbox) => {
-                    textbox.position.set(self.start.get_x(), self.start.get_y() + 1);
-                    format + &textbox.draw_format()
-                },
-                None => format,
-            }
-        }
-    }
-
-    fn action(&mut self) {
-        todo!()
+        self.view(format + &self.weekdays.draw_format())
     }
 
     fn get_start(&self) -> Position {
@@ -196,13 +301,53 @@ impl Widget for Calendar {
     }
 }
 
-fn get_weekdays(mut date: Date<Local>) -> String {
-    while date.weekday() != Weekday::Sun { date = date.succ(); }
+fn get_weekdays(week_start: Weekday) -> String {
     let mut text = String::new();
-    for _ in 1..=7 {
-        text.push_str(date.format("%a").to_string().as_str());
-        date = date.succ();
+    for weekday in month::get_weekdays(week_start).iter() {
+        // Any date on that weekday will do for its name
+        text.push_str(&NaiveDate::from_isoywd(2021, 1, *weekday).format("%a").to_string());
     }
     text.push(' ');
     text
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    use crate::{config::Config, position::{Direction, Position}, tui::ButtonType};
+
+    use super::Calendar;
+
+    fn get_selected(calendar: &Calendar) -> Option<NaiveDate> {
+        match calendar.buttons.get(calendar.cursor)?.button_data {
+            ButtonType::CalanderDate(date) => Some(date.naive_local()),
+            ButtonType::TextButton(_) => None,
+        }
+    }
+
+    #[test]
+    fn moves_by_date_when_a_day_has_no_button() {
+        let config = Config::default();
+        let mut calendar = Calendar::new(Local.ymd(2027, 3, 1), Position::new(1, 1), false, &config);
+        // Like a day whose midnight doesn't exist, every button after it is one index earlier
+        let missing = calendar.find_date(Local.ymd(2027, 3, 10)).unwrap();
+        calendar.buttons.remove(missing);
+        assert_eq!(calendar.find_date(Local.ymd(2027, 3, 10)), None);
+        assert_eq!(calendar.find_date(Local.ymd(2027, 3, 31)), Some(calendar.buttons.len() - 1));
+
+        let start = calendar.find_date(Local.ymd(2027, 3, 11)).unwrap();
+        calendar.select_button(&config, start);
+        calendar.move_cursor(&config, Direction::Right);
+        assert_eq!(get_selected(&calendar), Some(NaiveDate::from_ymd(2027, 3, 12)));
+        calendar.move_cursor(&config, Direction::Down);
+        assert_eq!(get_selected(&calendar), Some(NaiveDate::from_ymd(2027, 3, 19)));
+        calendar.move_cursor(&config, Direction::Left);
+        calendar.move_cursor(&config, Direction::Left);
+        assert_eq!(get_selected(&calendar), Some(NaiveDate::from_ymd(2027, 3, 17)));
+        // The missing day is a blank, going up through it leaves the days
+        assert_eq!(calendar.get_neighbour(Direction::Up), None);
+        calendar.move_cursor(&config, Direction::Up);
+        assert_eq!(calendar.cursor, 0);
+    }
+}
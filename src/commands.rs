@@ -0,0 +1,118 @@
+use crate::config::{self, THEMES};
+
+pub enum Command {
+    GoTo(String),
+    Export(String),
+    Import(String),
+    Theme(String),
+    Set(String, String),
+    Quit,
+}
+
+pub const COMMANDS: [&str; 6] = ["goto", "export", "import", "theme", "set", "quit"];
+
+pub fn parse_command(text: &str) -> Result<Command, String> {
+    let text = text.trim();
+    let (name, argument) = match text.find(' ') {
+        Some(index) => (&text[..index], text[index..].trim()),
+        None => (text, ""),
+    };
+    let name = match find_command(name) {
+        Some(name) => name,
+        None => return Err(format!("Unknown command \"{}\"", name)),
+    };
+    if argument.is_empty() && name != "quit" {
+        return Err(format!("Usage: {}", get_usage(name)));
+    }
+    match name {
+        "goto" => Ok(Command::GoTo(argument.to_string())),
+        "export" => Ok(Command::Export(argument.to_string())),
+        "import" => Ok(Command::Import(argument.to_string())),
+        "theme" => Ok(Command::Theme(argument.to_lowercase())),
+        "set" => match argument.find('=') {
+            Some(index) => Ok(Command::Set(argument[..index].trim().to_string(), argument[index + 1..].to_string())),
+            None => Err(format!("Usage: {}", get_usage(name))),
+        },
+        _ => Ok(Command::Quit),
+    }
+}
+
+// Commands can be shortened to any unique prefix, like :q or :go
+fn find_command(name: &str) -> Option<&'static str> {
+    if name.is_empty() { return None; }
+    let mut found = COMMANDS.iter().filter(|command| command.starts_with(name));
+    match (found.next(), found.next()) {
+        (Some(command), None) => Some(command),
+        _ => None,
+    }
+}
+
+fn get_usage(name: &str) -> &'static str {
+    match name {
+        "goto" => "goto <date>",
+        "export" => "export <file>",
+        "import" => "import <file>",
+        "theme" => "theme <name>",
+        "set" => "set <variable>=<value>",
+        _ => "quit",
+    }
+}
+
+pub fn complete(text: &str) -> Vec<String> {
+    let index = match text.find(' ') {
+        Some(index) => index,
+        None => {
+            return COMMANDS.iter()
+            .filter(|command| command.starts_with(text))
+            .map(|command| format!("{} ", command))
+            .collect();
+        },
+    };
+    let argument = text[index..].trim_start();
+    match find_command(&text[..index]) {
+        Some("theme") => THEMES.iter()
+            .filter(|(name, _)| name.starts_with(argument))
+            .map(|(name, _)| format!("theme {}", name))
+            .collect(),
+        Some("set") if !argument.contains('=') => config::get_variables().iter()
+            .filter(|variable| variable.starts_with(argument))
+            .map(|variable| format!("set {}=", variable))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, complete, parse_command};
+
+    #[test]
+    fn parses_goto_set_and_theme() {
+        assert!(matches!(parse_command("goto 14 mar 2027"), Ok(Command::GoTo(date)) if date == "14 mar 2027"));
+        assert!(matches!(parse_command("  go 2027-03 "), Ok(Command::GoTo(date)) if date == "2027-03"));
+        // The value is left for Config::set to clean up
+        assert!(matches!(parse_command("set week_start = mon"), Ok(Command::Set(name, value)) if name == "week_start" && value == " mon"));
+        assert!(matches!(parse_command("theme Dark"), Ok(Command::Theme(name)) if name == "dark"));
+        assert!(matches!(parse_command("q"), Ok(Command::Quit)));
+    }
+
+    #[test]
+    fn explains_bad_commands() {
+        assert_eq!(parse_command("goto").err(), Some(String::from("Usage: goto <date>")));
+        assert_eq!(parse_command("set week_start").err(), Some(String::from("Usage: set <variable>=<value>")));
+        assert_eq!(parse_command("theme").err(), Some(String::from("Usage: theme <name>")));
+        assert_eq!(parse_command("fly away").err(), Some(String::from("Unknown command \"fly\"")));
+        assert_eq!(parse_command("").err(), Some(String::from("Unknown command \"\"")));
+    }
+
+    #[test]
+    fn completes_names_and_arguments() {
+        assert_eq!(complete("g"), vec![String::from("goto ")]);
+        assert_eq!(complete("t"), vec![String::from("theme ")]);
+        assert_eq!(complete("theme d"), vec![String::from("theme default"), String::from("theme dark")]);
+        assert_eq!(complete("th oc"), vec![String::from("theme ocean")]);
+        assert_eq!(complete("set week_s"), vec![String::from("set week_start=")]);
+        assert!(complete("set week_start=m").is_empty());
+        assert!(complete("goto 2027").is_empty());
+    }
+}
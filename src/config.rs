@@ -1,7 +1,12 @@
-use std::{env, fs::File, io::{BufRead, BufReader}, sync::{Arc, Mutex}, thread};
+//! Reading config.txt, reloading it when it changes and the `:set` variables it shares with the TUI.
 
+use std::{env, fs, io::ErrorKind, path::PathBuf, sync::{Arc, Mutex}, thread, time::Duration};
+
+use chrono::Weekday;
 use termion::{color::AnsiValue, event::Key};
 
+use crate::{error::{Error, Result}, keymap::{ACTIONS, Action, Gesture, Input, KeyMode, Keymap}};
+
 /*
 Ansi value for color
 Black, 0
@@ -22,7 +27,47 @@ LightCyan, 14
 LightWhite, 15
 */
 
-#[derive(Clone, Copy)]
+/// Every variable other than key bindings that can be set in config.txt or with :set
+pub const VARIABLES: &[&str] = &[
+    "bg_color", "calendar_bg_color", "date_bg_color", "text_button_bg_color", "date_num_color",
+    "month_text_color", "weekday_bg_color", "select_bg_date_color", "select_bg_text_button_color",
+    "prompt_bg_color", "prompt_text_color", "prompt_error_color", "list_bg_color", "list_text_color",
+    "list_select_color", "range_bg_color", "status_bg_color", "status_text_color", "status_error_color",
+    "change_calendar_reset_cursor", "unselect_change_calendar_cursor", "osc52_clipboard", "max_threads",
+    "double_click_ms", "status_message_ms", "undo_limit", "key_timeout_ms", "week_start", "scroll_mode",
+    "smooth_scroll", "scroll_speed",
+];
+
+/// Named color sets, each entry is applied like a line of config.txt
+pub const THEMES: &[(&str, &[(&str, &str)])] = &[
+    ("default", &[
+        ("bg_color", "lightblue"), ("calendar_bg_color", "white"), ("date_bg_color", "black"),
+        ("text_button_bg_color", "cyan"), ("date_num_color", "white"), ("month_text_color", "white"),
+        ("weekday_bg_color", "lightred"), ("select_bg_date_color", "magenta"),
+        ("select_bg_text_button_color", "lightmagenta"),
+    ]),
+    ("dark", &[
+        ("bg_color", "black"), ("calendar_bg_color", "gray4"), ("date_bg_color", "gray8"),
+        ("text_button_bg_color", "gray8"), ("date_num_color", "lightwhite"), ("month_text_color", "lightcyan"),
+        ("weekday_bg_color", "gray6"), ("select_bg_date_color", "blue"),
+        ("select_bg_text_button_color", "lightblue"),
+    ]),
+    ("light", &[
+        ("bg_color", "gray22"), ("calendar_bg_color", "lightwhite"), ("date_bg_color", "gray20"),
+        ("text_button_bg_color", "lightcyan"), ("date_num_color", "black"), ("month_text_color", "black"),
+        ("weekday_bg_color", "lightyellow"), ("select_bg_date_color", "lightgreen"),
+        ("select_bg_text_button_color", "lightgreen"),
+    ]),
+    ("ocean", &[
+        ("bg_color", "rgb(0,1,3)"), ("calendar_bg_color", "rgb(0,2,4)"), ("date_bg_color", "rgb(0,1,2)"),
+        ("text_button_bg_color", "rgb(0,3,4)"), ("date_num_color", "lightwhite"), ("month_text_color", "black"),
+        ("weekday_bg_color", "rgb(0,4,5)"), ("select_bg_date_color", "rgb(5,3,0)"),
+        ("select_bg_text_button_color", "rgb(5,4,1)"),
+    ]),
+];
+
+/// Everything config.txt can set, starting from the defaults
+#[derive(Clone)]
 pub struct Config {
     pub bg_color: AnsiValue,
     pub calendar_bg_color: AnsiValue,
@@ -33,40 +78,47 @@ pub struct Config {
     pub weekday_bg_color: AnsiValue,
     pub select_bg_date_color: AnsiValue,
     pub select_bg_text_button_color: AnsiValue,
-    pub quit: Key,
-    pub edit: Key,
-    pub up: Key,
-    pub left: Key,
-    pub down: Key,
-    pub right: Key,
-    pub calendar_up: Key,
-    pub calendar_left: Key,
-    pub calendar_right: Key,
-    pub calendar_down: Key,
-    pub go_back_time: Key,
-    pub go_forward_time: Key,
-    pub go_back_calendar: Key,
-    pub go_forward_calendar: Key,
+    pub prompt_bg_color: AnsiValue,
+    pub prompt_text_color: AnsiValue,
+    pub prompt_error_color: AnsiValue,
+    pub list_bg_color: AnsiValue,
+    pub list_text_color: AnsiValue,
+    pub list_select_color: AnsiValue,
+    pub status_bg_color: AnsiValue,
+    pub status_text_color: AnsiValue,
+    pub status_error_color: AnsiValue,
+    pub range_bg_color: AnsiValue,
+    pub keymap: Keymap,
     pub change_calendar_reset_cursor: bool,
     pub unselect_change_calendar_cursor: bool,
+    pub osc52_clipboard: bool,
+    pub scroll_mode: bool, // Months scroll by rows like a list instead of jumping a screen at a time
+    pub smooth_scroll: bool,
+    pub scroll_speed: u16, // Lines per wheel step with smooth scrolling
     pub max_threads: usize,
+    pub double_click_ms: usize,
+    pub status_message_ms: usize,
+    pub undo_limit: usize,
+    pub week_start: Weekday,
     // TODO have buttons to move calander right left etc
 }
 
 impl Config {
-    pub fn get_config() -> Self {
-        // TODO handle errors
+    /// No config file just means the defaults, one that can't be read is an error
+    pub fn get_config() -> Result<Self> {
+        match fs::read_to_string(get_path("config.txt")) {
+            Ok(text) => Config::parse(&text),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Config::get_default_config()),
+            Err(error) => Err(Error::Config(error)),
+        }
+    }
+
+    /// The defaults changed by the lines of a config file, lines that aren't understood are skipped
+    pub fn parse(text: &str) -> Result<Self> {
         let config = Config::get_default_config();
-        let file = env::current_exe().unwrap().parent().unwrap().join("config.txt"); 
-        // TODO this path is dumb. Instead store it either in .cofing, in current_dir() or specified by user
-        if !file.exists() { return config };
-        let file = match File::open(file) {
-            Ok(file) => file,
-            Err(_) => return config,
-        };
-        let reader = BufReader::new(file);
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
         let mut handles = Vec::with_capacity(10);
-        let mutex = Arc::new(Mutex::new(reader.lines()));
+        let mutex = Arc::new(Mutex::new(lines.into_iter()));
         let config_mutex = Arc::new(Mutex::new(config));
         for _ in 0..10 { // TODO can we somehow get max threads first?
             let mutex = mutex.clone();
@@ -76,41 +128,45 @@ impl Config {
                     let line;
                     { 
                         match mutex.lock().unwrap().next() {
-                            Some(value) => line = value.unwrap(),
+                            Some(value) => line = value,
                             None => break,
                         }
                     }
-                    let line = line.trim().to_lowercase();
+                    let line = line.trim();
                     if line.starts_with('#') { continue }
-                    let split_index;
-                    match line.find('=') {
-                        Some(index) => split_index = index,
+                    let split_index = match line.find('=') {
+                        Some(index) => index,
                         None => continue,
-                    }
+                    };
                     let (config_var, value) = line.split_at(split_index);
-                    let value = value.replace(" ", "").replace("=", "");
-                    let config_var = config_var.trim();
-                    let value = value.trim();
-                    {
-                        let mut config = config_mutex.lock().unwrap();
-                        if config.match_boolean(config_var, value)
-                        || config.match_number(config_var, value)
-                        || config.match_colors(config_var, value)
-                        || config.match_key(config_var, value) 
-                        { continue }
-                    }
+                    config_mutex.lock().unwrap().set(config_var, value);
                 }
             });
             handles.push(handle);
         }
 
         for handle in handles {
-            handle.join().unwrap();
+            handle.join().map_err(|_| Error::Worker)?;
         }
 
-        if let Ok(lock) = Arc::try_unwrap(config_mutex) {
-            lock.into_inner().unwrap()
-        } else { config }
+        match Arc::try_unwrap(config_mutex) {
+            Ok(lock) => lock.into_inner().map_err(|_| Error::Worker),
+            Err(_) => Ok(Config::get_default_config()),
+        }
+    }
+
+    /// Sets a variable or key binding from its config.txt name, false when either is not understood
+    pub fn set(&mut self, config_var: &str, value: &str) -> bool {
+        // Keys keep their case so N and n can be told apart
+        let key_value = value.replace([' ', '='], "");
+        let value = key_value.to_lowercase();
+        let config_var = config_var.trim().to_lowercase();
+        let config_var = config_var.as_str();
+        self.match_boolean(config_var, &value)
+        || self.match_number(config_var, &value)
+        || self.match_colors(config_var, &value)
+        || self.match_key(config_var, &key_value)
+        || self.match_weekday(config_var, &value)
     }
 
     fn get_default_config() -> Self {
@@ -124,23 +180,28 @@ impl Config {
             weekday_bg_color: AnsiValue(9),
             select_bg_date_color: AnsiValue(5),
             select_bg_text_button_color: AnsiValue(13),
-            quit: Key::Char('q'),
-            edit: Key::Char('\n'),
-            up: Key::Char('w'),
-            left: Key::Char('a'),
-            down: Key::Char('s'),
-            right: Key::Char('d'),
-            calendar_up: Key::Char('W'),
-            calendar_left: Key::Char('A'),
-            calendar_down: Key::Char('S'),
-            calendar_right: Key::Char('D'),
-            go_back_time: Key::Left,
-            go_forward_time: Key::Right,
-            go_back_calendar: Key::Down,
-            go_forward_calendar: Key::Up,
+            prompt_bg_color: AnsiValue(0),
+            prompt_text_color: AnsiValue(15),
+            prompt_error_color: AnsiValue(9),
+            list_bg_color: AnsiValue(0),
+            list_text_color: AnsiValue(15),
+            list_select_color: AnsiValue(5),
+            status_bg_color: AnsiValue(0),
+            status_text_color: AnsiValue(15),
+            status_error_color: AnsiValue(9),
+            range_bg_color: AnsiValue(2),
+            keymap: Keymap::new(),
             change_calendar_reset_cursor: true,
             unselect_change_calendar_cursor: true,
+            osc52_clipboard: true,
+            scroll_mode: false,
+            smooth_scroll: true,
+            scroll_speed: 3,
             max_threads: 1,
+            double_click_ms: 400,
+            status_message_ms: 3000,
+            undo_limit: 100,
+            week_start: Weekday::Sun,
         }
     }
 
@@ -150,6 +211,9 @@ impl Config {
             match config_var {
                 "change_calendar_reset_cursor" => config.change_calendar_reset_cursor = value,
                 "unselect_change_calendar_cursor" => config.unselect_change_calendar_cursor = value,
+                "osc52_clipboard" => config.osc52_clipboard = value,
+                "scroll_mode" => config.scroll_mode = value,
+                "smooth_scroll" => config.smooth_scroll = value,
                 _ => return false,
             }
             return true;
@@ -162,6 +226,11 @@ impl Config {
         if let Ok(value) = value.parse::<usize>() {
             match config_var {
                 "max_threads" => if value != 0 { config.max_threads = value },
+                "double_click_ms" => config.double_click_ms = value,
+                "status_message_ms" => config.status_message_ms = value,
+                "undo_limit" => config.undo_limit = value,
+                "scroll_speed" => if value != 0 { config.scroll_speed = value.min(u16::MAX as usize) as u16 },
+                "key_timeout_ms" => config.keymap.timeout = Duration::from_millis(value as u64),
                 _ => return false,
             }
             return true;
@@ -182,6 +251,16 @@ impl Config {
                 "weekday_bg_color" => config.weekday_bg_color = value,
                 "select_bg_date_color" => config.select_bg_date_color = value,
                 "select_bg_text_button_color" => config.select_bg_text_button_color = value,
+                "prompt_bg_color" => config.prompt_bg_color = value,
+                "prompt_text_color" => config.prompt_text_color = value,
+                "prompt_error_color" => config.prompt_error_color = value,
+                "list_bg_color" => config.list_bg_color = value,
+                "list_text_color" => config.list_text_color = value,
+                "list_select_color" => config.list_select_color = value,
+                "status_bg_color" => config.status_bg_color = value,
+                "status_text_color" => config.status_text_color = value,
+                "status_error_color" => config.status_error_color = value,
+                "range_bg_color" => config.range_bg_color = value,
                 _ => return false,
             }
             return true
@@ -190,23 +269,28 @@ impl Config {
     }
 
     fn match_key(&mut self, config_var: &str, value: &str) -> bool {
+        let mut mode = KeyMode::Normal;
+        let mut name = config_var;
+        for key_mode in [KeyMode::Visual, KeyMode::Command] {
+            if let Some(action) = config_var.strip_prefix(key_mode.get_prefix()) {
+                mode = key_mode;
+                name = action;
+            }
+        }
+        match (Action::from_name(name), parse_inputs(value)) {
+            (Some(action), Some(inputs)) => {
+                self.keymap.bind(mode, action, inputs);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn match_weekday(&mut self, config_var: &str, value: &str) -> bool {
         let config = self;
-        if let Some(key) = parse_key(value) {
+        if let Ok(value) = value.parse::<Weekday>() {
             match config_var {
-                "quit" => config.quit = key,
-                "edit" => config.edit = key,
-                "up" => config.up = key,
-                "left" => config.left = key,
-                "down" => config.down = key,
-                "right" => config.right = key,
-                "calendar_up" => config.calendar_up = key,
-                "calendar_left" => config.calendar_left = key,
-                "calendar_right" => config.calendar_right = key,
-                "calendar_down" => config.calendar_down = key,
-                "go_back_time" => config.go_back_time = key,
-                "go_forward_time" => config.go_forward_time = key,
-                "go_back_calendar" => config.go_back_calendar = key,
-                "go_forward_calendar" => config.go_forward_calendar = key,
+                "week_start" => config.week_start = value,
                 _ => return false,
             }
             return true
@@ -215,9 +299,41 @@ impl Config {
     }
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Config::get_default_config()
+    }
+}
+
+/// VARIABLES plus a key binding for every action in every mode
+pub fn get_variables() -> Vec<String> {
+    let mut variables: Vec<String> = VARIABLES.iter().map(|variable| variable.to_string()).collect();
+    for mode in [KeyMode::Normal, KeyMode::Visual, KeyMode::Command] {
+        for action in ACTIONS.iter() {
+            variables.push(format!("{}{}", mode.get_prefix(), action.get_name()));
+        }
+    }
+    variables
+}
+
+/// The settings of a theme in THEMES
+pub fn get_theme(name: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    THEMES.iter().find(|(theme, _)| *theme == name).map(|(_, values)| *values)
+}
+
+/// Where a file of the calendar lives, next to the executable
+pub fn get_path(file_name: &str) -> PathBuf {
+    // TODO this path is dumb. Instead store it either in .cofing, in current_dir() or specified by user
+    // Fall back to the working directory if the executable can't be found
+    match env::current_exe() {
+        Ok(path) => path.parent().map_or_else(|| PathBuf::from(file_name), |parent| parent.join(file_name)),
+        Err(_) => PathBuf::from(file_name),
+    }
+}
+
 fn parse_boolean(color_string: &str) -> Option<bool> {
     if let Ok(bool) = color_string.parse::<bool>() {
-        return Some(bool);
+        Some(bool)
     } else {
         match color_string {
             "no" | "n" | "f" | "!true" | "nottrue" | "deny" | "negative" | "out" | ":(" | ":#" =>
@@ -293,13 +409,48 @@ fn parse_gray_shade(color_string: &str) -> Option<AnsiValue> {
     }
 }
 
+// Comma separated keys and mouse gestures like h,<left>,ctrl+wheel_up
+fn parse_inputs(inputs_string: &str) -> Option<Vec<Input>> {
+    if inputs_string == "," { return Some(vec![Input::Keys(vec![Key::Char(',')])]); }
+    inputs_string.split(',').map(|input| {
+        match Gesture::from_name(input) {
+            Some(gesture) => Some(Input::Mouse(gesture)),
+            None => parse_keys(input).map(Input::Keys),
+        }
+    }).collect()
+}
+
+// A sequence like gg, <up><up> or ctrl(w)k, a single key name like esc is also one key
+fn parse_keys(keys_string: &str) -> Option<Vec<Key>> {
+    if let Some(key) = parse_key(keys_string) {
+        return Some(vec![key]);
+    }
+    let mut keys = Vec::new();
+    let mut rest = keys_string;
+    while let Some(char) = rest.chars().next() {
+        let end = if char == '<' {
+            rest.find('>')? + 1
+        } else if let Some(index) = rest.find('(').filter(|&index| rest[..index].chars().all(char::is_alphabetic)) {
+            index + rest[index..].find(')')? + 1
+        } else {
+            char.len_utf8()
+        };
+        let key = rest[..end].trim_start_matches('<').trim_end_matches('>');
+        keys.push(parse_key(key)?);
+        rest = &rest[end..];
+    }
+    if keys.is_empty() { None } else { Some(keys) }
+}
+
 fn parse_key(key_string: &str) -> Option<Key> {
     if let Ok(char) = key_string.parse::<char>(){
         return Some(Key::Char(char));
-    } else if key_string.contains('(') && key_string.contains(')') {
-        let index = key_string.find("(").unwrap();
+    }
+    let key_string = key_string.to_lowercase();
+    if key_string.contains('(') && key_string.contains(')') {
+        let index = key_string.find('(').unwrap();
         let (key, value) = key_string.split_at(index);
-        let value = value.replace('(', "").replace(')', "");
+        let value = value.replace(['(', ')'], "");
         match key {
             "alt" => 
                 if let Ok(char) = value.parse::<char>() {
@@ -316,7 +467,7 @@ fn parse_key(key_string: &str) -> Option<Key> {
             _ => (),
         }
     } else {
-        return match key_string {
+        return match key_string.as_str() {
             "backspace" | "back space" => Some(Key::Backspace),
             "left" => Some(Key::Left),
             "right" => Some(Key::Right),
@@ -330,6 +481,10 @@ fn parse_key(key_string: &str) -> Option<Key> {
             "delete" => Some(Key::Delete),
             "insert" => Some(Key::Insert),
             "esc" | "escape" => Some(Key::Esc),
+            "enter" | "return" => Some(Key::Char('\n')),
+            "tab" => Some(Key::Char('\t')),
+            "space" => Some(Key::Char(' ')),
+            "comma" => Some(Key::Char(',')),
             "null" => Some(Key::Null), // What is the null byte?
             _ => None,
         }
@@ -0,0 +1,141 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::month::Month;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+// Accepts "2027-03-14", "2027-03", "14/03", "14/03/2027", "mar 2027", "14 mar 2027" and "march"
+pub fn parse_date(text: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() { return Err(String::from("Type a date like 2027-03-14, 14/03 or mar 2027")); }
+    if text.contains('-') {
+        let parts: Vec<&str> = text.split('-').map(|part| part.trim()).collect();
+        match parts.len() {
+            2 => make_date(parse_year(parts[0])?, parse_month(parts[1])?, 1),
+            3 => make_date(parse_year(parts[0])?, parse_month(parts[1])?, parse_day(parts[2])?),
+            _ => Err(format!("Can't read \"{}\", use year-month-day", text)),
+        }
+    } else if text.contains('/') {
+        let parts: Vec<&str> = text.split('/').map(|part| part.trim()).collect();
+        match parts.len() {
+            2 => make_date(today.year(), parse_month(parts[1])?, parse_day(parts[0])?),
+            3 => make_date(parse_year(parts[2])?, parse_month(parts[1])?, parse_day(parts[0])?),
+            _ => Err(format!("Can't read \"{}\", use day/month/year", text)),
+        }
+    } else {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        // An optional day comes before the month name and an optional year after it
+        let (day, parts) = match parts.first().map(|part| part.parse::<u32>()) {
+            Some(Ok(_)) if parts.len() > 1 => (parse_day(parts[0])?, &parts[1..]),
+            _ => (1, &parts[..]),
+        };
+        match parts.len() {
+            1 => make_date(today.year(), parse_month(parts[0])?, day),
+            2 => make_date(parse_year(parts[1])?, parse_month(parts[0])?, day),
+            _ => Err(format!("Can't read \"{}\", use a month name and year", text)),
+        }
+    }
+}
+
+fn parse_year(text: &str) -> Result<i32, String> {
+    match text.parse::<i32>() {
+        Ok(year) => Ok(year),
+        Err(_) => Err(format!("\"{}\" is not a year", text)),
+    }
+}
+
+fn parse_month(text: &str) -> Result<u32, String> {
+    if let Ok(month) = text.parse::<u32>() {
+        return if (1..=12).contains(&month) { Ok(month) }
+        else { Err(format!("Month {} is not between 1 and 12", month)) };
+    }
+    if text.len() >= 3 {
+        for (index, name) in MONTHS.iter().enumerate() {
+            if text.starts_with(name) { return Ok(index as u32 + 1); }
+        }
+    }
+    Err(format!("\"{}\" is not a month", text))
+}
+
+fn parse_day(text: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(day) if day > 0 => Ok(day),
+        _ => Err(format!("\"{}\" is not a day", text)),
+    }
+}
+
+fn make_date(year: i32, month: u32, day: u32) -> Result<NaiveDate, String> {
+    match NaiveDate::from_ymd_opt(year, month, day) {
+        // The weeks around the very first and last months chrono knows can't be laid out
+        Some(date) if Month::containing(date, Weekday::Mon).is_none() => Err(format!("Year {} is out of range", year)),
+        Some(date) => Ok(date),
+        None => match NaiveDate::from_ymd_opt(year, month, 1) {
+            Some(date) => Err(format!("{} only has {} days", date.format("%B %Y"), days_in_month(date))),
+            None => Err(format!("Year {} is out of range", year)),
+        },
+    }
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let mut date = date.with_day(1).unwrap();
+    let month = date.month();
+    let mut days = 0;
+    while date.month() == month {
+        days += 1;
+        date = date.succ();
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::parse_date;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2026, 10, 18)
+    }
+
+    #[test]
+    fn reads_every_format() {
+        let cases = [
+            ("2027-03-14", (2027, 3, 14)),
+            (" 2027-3-4 ", (2027, 3, 4)),
+            ("2027-03", (2027, 3, 1)),
+            ("14/03", (2026, 3, 14)),
+            ("14/03/2027", (2027, 3, 14)),
+            ("mar 2027", (2027, 3, 1)),
+            ("MARCH 2027", (2027, 3, 1)),
+            ("14 mar 2027", (2027, 3, 14)),
+            ("14 march", (2026, 3, 14)),
+            ("march", (2026, 3, 1)),
+            ("2024-02-29", (2024, 2, 29)),
+        ];
+        for (text, (year, month, day)) in cases.iter() {
+            assert_eq!(parse_date(text, today()), Ok(NaiveDate::from_ymd(*year, *month, *day)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn explains_what_is_wrong() {
+        let cases = [
+            ("", "Type a date like 2027-03-14, 14/03 or mar 2027"),
+            ("2027-13-01", "Month 13 is not between 1 and 12"),
+            ("2027-02-30", "February 2027 only has 28 days"),
+            ("2027-02-0", "\"0\" is not a day"),
+            ("abc-03", "\"abc\" is not a year"),
+            ("2027-03-14-1", "Can't read \"2027-03-14-1\", use year-month-day"),
+            ("1/2/3/4", "Can't read \"1/2/3/4\", use day/month/year"),
+            ("31/04", "April 2026 only has 30 days"),
+            ("foo", "\"foo\" is not a month"),
+            ("ma", "\"ma\" is not a month"),
+            ("14 mar 2027 5", "Can't read \"14 mar 2027 5\", use a month name and year"),
+            ("262143-12-01", "Year 262143 is out of range"),
+            ("300000-01-01", "Year 300000 is out of range"),
+        ];
+        for (text, error) in cases.iter() {
+            assert_eq!(parse_date(text, today()), Err(error.to_string()), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! The errors that keep the calendar from starting or running.
+
+use std::{fmt, io};
+
+/// Anything that stops the calendar from running, shown to the user instead of a panic
+#[derive(Debug)]
+pub enum Error {
+    NoTty(io::Error),
+    TerminalSize(io::Error),
+    RawMode(io::Error),
+    Config(io::Error),
+    Worker, // A thread building or drawing calendars panicked
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoTty(_) => write!(formatter, "no controlling TTY"),
+            Error::TerminalSize(error) => write!(formatter, "could not get the terminal size: {}", describe(error)),
+            Error::RawMode(error) => write!(formatter, "could not set up the terminal: {}", describe(error)),
+            Error::Config(error) => write!(formatter, "config unreadable: {}", describe(error)),
+            Error::Worker => write!(formatter, "a worker thread crashed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NoTty(error) | Error::TerminalSize(error) | Error::RawMode(error) | Error::Config(error) => Some(error),
+            Error::Worker => None,
+        }
+    }
+}
+
+// OS errors read like "permission denied" without the error number
+fn describe(error: &io::Error) -> String {
+    if error.raw_os_error().is_some() { error.kind().to_string() } else { error.to_string() }
+}
@@ -0,0 +1,89 @@
+use std::{fs, path::Path, sync::{Arc, Mutex, mpsc::Sender}, thread, time::{Duration, SystemTime}};
+
+use signal_hook::{consts::{SIGHUP, SIGTERM, SIGTSTP, SIGWINCH}, iterator::Signals, low_level};
+use termion::event::Event;
+
+use crate::{config, terminal::EventSource};
+
+// How often the watched files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub enum TuiEvent {
+    Input(Event),
+    Resize,
+    Suspend,
+    Terminate,
+    NotesChanged,
+    ConfigChanged,
+}
+
+pub fn spawn_input(tx: Arc<Mutex<Sender<TuiEvent>>>, events: EventSource) {
+    thread::spawn(move || {
+        for event in events {
+            let event = 
+            match event {
+                Ok(event) => event,
+                Err(_) => continue,
+            };
+            // I use lock to stop this thread when I need to edit
+            // If I let it keep sending it causes lag for editor input
+            match tx.try_lock(){
+                Ok(tx) => if tx.send(TuiEvent::Input(event)).is_err() { break },
+                Err(_) => {
+                    // Wait until lock is ok and do nothing in that time
+                    let _lock = tx.lock().unwrap(); 
+                }, 
+            }
+        }
+    });
+}
+
+// Signals are turned into events so the main loop can put the terminal back before acting on them
+pub fn spawn_signals(tx: Sender<TuiEvent>) {
+    let mut signals = 
+    match Signals::new([SIGWINCH, SIGTSTP, SIGTERM, SIGHUP]) {
+        Ok(signals) => signals,
+        Err(_) => return,
+    };
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let event = match signal {
+                SIGWINCH => TuiEvent::Resize,
+                SIGTSTP => TuiEvent::Suspend,
+                _ => TuiEvent::Terminate,
+            };
+            if tx.send(event).is_err() { break; }
+        }
+    });
+}
+
+// Stops the whole process like ctrl+z normally would, returns once it gets continued with fg
+pub fn stop_process() {
+    let _ = low_level::emulate_default_handler(SIGTSTP);
+}
+
+// Polls the modified time of the notes and config files so edits from outside show up. Our own
+// note saves change it as well, the Tui skips those with Notes::is_changed_on_disk
+pub fn spawn_file_watch(tx: Sender<TuiEvent>) {
+    thread::spawn(move || {
+        let files = [
+            (config::get_path("notes.txt"), TuiEvent::NotesChanged),
+            (config::get_path("config.txt"), TuiEvent::ConfigChanged),
+        ];
+        let mut times: Vec<Option<SystemTime>> = files.iter().map(|(file, _)| get_modified(file)).collect();
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            for ((file, event), time) in files.iter().zip(times.iter_mut()) {
+                let modified = get_modified(file);
+                if modified == *time { continue; }
+                *time = modified;
+                if tx.send(event.clone()).is_err() { return; }
+            }
+        }
+    });
+}
+
+fn get_modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|metadata| metadata.modified()).ok()
+}
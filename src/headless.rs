@@ -0,0 +1,139 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{buffer::Buffer, error::Result, position::Position, terminal::{Backend, EventSource}};
+
+enum Parser {
+    Text,
+    Escape,
+    Csi(String),
+    Osc,
+    OscEscape,
+}
+
+struct Screen {
+    buffer: Buffer,
+    parser: Parser,
+}
+
+// In-memory terminal that reads the escape codes written to it into a grid of cells
+// Clones share the same screen so one can be kept to look at what was drawn
+#[derive(Clone)]
+pub struct VirtualBackend {
+    screen: Arc<Mutex<Screen>>,
+}
+
+impl VirtualBackend {
+    pub fn new(size: Position) -> Self {
+        let screen = Screen { buffer: Buffer::new(size), parser: Parser::Text };
+        VirtualBackend { screen: Arc::new(Mutex::new(screen)) }
+    }
+
+    pub fn get_lines(&self) -> Vec<String> {
+        let screen = self.screen.lock().unwrap();
+        let size = screen.buffer.get_size();
+        (1..=size.get_y()).map(|y| {
+            (1..=size.get_x()).filter_map(|x| screen.buffer.get(x, y)).map(|cell| cell.char).collect::<String>()
+            .trim_end().to_string()
+        }).collect()
+    }
+}
+
+impl Screen {
+    fn read(&mut self, char: char) {
+        self.parser = match std::mem::replace(&mut self.parser, Parser::Text) {
+            Parser::Text => match char {
+                '\x1b' => Parser::Escape,
+                _ => {
+                    self.buffer.write(&char.to_string());
+                    Parser::Text
+                },
+            },
+            Parser::Escape => match char {
+                '[' => Parser::Csi(String::new()),
+                ']' => Parser::Osc,
+                _ => Parser::Text,
+            },
+            Parser::Csi(mut parameters) => match char {
+                '\x40'..='\x7e' => {
+                    self.run_csi(&parameters, char);
+                    Parser::Text
+                },
+                _ => {
+                    parameters.push(char);
+                    Parser::Csi(parameters)
+                },
+            },
+            // Operating system commands like the clipboard end with a bell or ESC \
+            Parser::Osc => match char {
+                '\x07' => Parser::Text,
+                '\x1b' => Parser::OscEscape,
+                _ => Parser::Osc,
+            },
+            Parser::OscEscape => Parser::Text,
+        };
+    }
+
+    fn run_csi(&mut self, parameters: &str, command: char) {
+        // Private modes like ?25l for the cursor or ?1000h for the mouse don't change the grid
+        if parameters.starts_with('?') { return; }
+        let numbers: Vec<u16> = parameters.split(';').map(|number| number.parse().unwrap_or(0)).collect();
+        match command {
+            'H' => {
+                let y = numbers.first().copied().unwrap_or(1).max(1);
+                let x = numbers.get(1).copied().unwrap_or(1).max(1);
+                self.buffer.go_to(x, y);
+            },
+            'J' if numbers.first() == Some(&2) => self.buffer.clear(),
+            'm' => self.run_sgr(&numbers),
+            _ => (),
+        }
+    }
+
+    // Only the colors the renderer uses: 38;5;n, 48;5;n, the defaults and a full reset
+    fn run_sgr(&mut self, numbers: &[u16]) {
+        let mut numbers = numbers.iter();
+        while let Some(number) = numbers.next() {
+            match number {
+                0 => {
+                    self.buffer.set_bg(None);
+                    self.buffer.set_fg(None);
+                },
+                38 | 48 => {
+                    let color = match (numbers.next(), numbers.next()) {
+                        (Some(5), Some(color)) => Some(*color as u8),
+                        _ => None,
+                    };
+                    if *number == 38 { self.buffer.set_fg(color) } else { self.buffer.set_bg(color) }
+                },
+                39 => self.buffer.set_fg(None),
+                49 => self.buffer.set_bg(None),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Backend for VirtualBackend {
+    fn write(&mut self, text: &str) {
+        let mut screen = self.screen.lock().unwrap();
+        for char in text.chars() {
+            screen.read(char);
+        }
+    }
+
+    fn get_size(&self) -> Result<Position> {
+        Ok(self.screen.lock().unwrap().buffer.get_size())
+    }
+
+    fn set_raw(&mut self, _raw: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_mouse(&mut self, _mouse: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_events(&self) -> Option<EventSource> {
+        None
+    }
+}
@@ -0,0 +1,639 @@
+//! Actions, the keys and mouse gestures bound to them, and counts and sequences like 5d or gg.
+
+use std::time::{Duration, Instant};
+
+use termion::event::{Key, MouseButton};
+
+// Keeps a typo like 99999999d from looping for ages
+const MAX_COUNT: usize = 9999;
+
+/// Everything a key or mouse binding can do
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    Edit,
+    Up,
+    Left,
+    Down,
+    Right,
+    CalendarUp,
+    CalendarLeft,
+    CalendarRight,
+    CalendarDown,
+    GoBackTime,
+    GoForwardTime,
+    GoBackCalendar,
+    GoForwardCalendar,
+    Today,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    GoToDate,
+    CommandLine,
+    Visual,
+    Undo,
+    Redo,
+    Yank,
+    Cut,
+    Paste,
+    Register,
+    Submit,
+    Cancel,
+    Complete,
+    HistoryBack,
+    HistoryForward,
+    Delete,
+    Help,
+    Suspend,
+}
+
+pub const ACTIONS: [Action; 35] = [
+    Action::Quit, Action::Edit, Action::Up, Action::Left, Action::Down, Action::Right,
+    Action::CalendarUp, Action::CalendarLeft, Action::CalendarRight, Action::CalendarDown,
+    Action::GoBackTime, Action::GoForwardTime, Action::GoBackCalendar, Action::GoForwardCalendar,
+    Action::Today, Action::Search, Action::NextMatch, Action::PreviousMatch, Action::GoToDate,
+    Action::CommandLine, Action::Visual, Action::Undo, Action::Redo, Action::Yank, Action::Cut,
+    Action::Paste, Action::Register, Action::Submit, Action::Cancel, Action::Complete,
+    Action::HistoryBack, Action::HistoryForward, Action::Delete, Action::Help,
+    Action::Suspend,
+];
+
+impl Action {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Edit => "edit",
+            Action::Up => "up",
+            Action::Left => "left",
+            Action::Down => "down",
+            Action::Right => "right",
+            Action::CalendarUp => "calendar_up",
+            Action::CalendarLeft => "calendar_left",
+            Action::CalendarRight => "calendar_right",
+            Action::CalendarDown => "calendar_down",
+            Action::GoBackTime => "go_back_time",
+            Action::GoForwardTime => "go_forward_time",
+            Action::GoBackCalendar => "go_back_calendar",
+            Action::GoForwardCalendar => "go_forward_calendar",
+            Action::Today => "today",
+            Action::Search => "search",
+            Action::NextMatch => "next_match",
+            Action::PreviousMatch => "previous_match",
+            Action::GoToDate => "go_to_date",
+            Action::CommandLine => "command_line",
+            Action::Visual => "visual",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Yank => "yank",
+            Action::Cut => "cut",
+            Action::Paste => "paste",
+            Action::Register => "register",
+            Action::Submit => "submit",
+            Action::Cancel => "cancel",
+            Action::Complete => "complete",
+            Action::HistoryBack => "history_back",
+            Action::HistoryForward => "history_forward",
+            Action::Delete => "delete",
+            Action::Help => "help",
+            Action::Suspend => "suspend",
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Edit => "Edit the note of the selected date",
+            Action::Up => "Move the cursor up",
+            Action::Left => "Move the cursor left",
+            Action::Down => "Move the cursor down",
+            Action::Right => "Move the cursor right",
+            Action::CalendarUp => "Move to the calendar above",
+            Action::CalendarLeft => "Move to the calendar on the left",
+            Action::CalendarRight => "Move to the calendar on the right",
+            Action::CalendarDown => "Move to the calendar below",
+            Action::GoBackTime => "Show the months before the screen",
+            Action::GoForwardTime => "Show the months after the screen",
+            Action::GoBackCalendar => "Go back one month",
+            Action::GoForwardCalendar => "Go forward one month",
+            Action::Today => "Go to today",
+            Action::Search => "Search notes",
+            Action::NextMatch => "Go to the next search match",
+            Action::PreviousMatch => "Go to the previous search match",
+            Action::GoToDate => "Go to a date",
+            Action::CommandLine => "Open the command line",
+            Action::Visual => "Select a range of dates",
+            Action::Undo => "Undo the last note change",
+            Action::Redo => "Redo the last undone change",
+            Action::Yank => "Copy the note into a register",
+            Action::Cut => "Cut the note into a register",
+            Action::Paste => "Paste a register into the note",
+            Action::Register => "Pick the register for the next yank, cut or paste",
+            Action::Submit => "Run the command",
+            Action::Cancel => "Cancel",
+            Action::Complete => "Complete the command",
+            Action::HistoryBack => "Previous command in history",
+            Action::HistoryForward => "Next command in history",
+            Action::Delete => "Delete a character",
+            Action::Help => "Show this help",
+            Action::Suspend => "Suspend to the shell, fg brings it back",
+        }
+    }
+
+    fn get_category(&self) -> &'static str {
+        match self {
+            Action::Up | Action::Left | Action::Down | Action::Right | Action::CalendarUp
+            | Action::CalendarLeft | Action::CalendarRight | Action::CalendarDown => "Movement",
+            Action::GoBackTime | Action::GoForwardTime | Action::GoBackCalendar | Action::GoForwardCalendar
+            | Action::Today | Action::GoToDate => "Time travel",
+            Action::Edit | Action::Visual | Action::Undo | Action::Redo | Action::Yank | Action::Cut
+            | Action::Paste | Action::Register => "Notes",
+            Action::Search | Action::NextMatch | Action::PreviousMatch => "Search",
+            Action::Submit | Action::Cancel | Action::Complete | Action::HistoryBack | Action::HistoryForward
+            | Action::Delete => "Command line",
+            Action::CommandLine | Action::Help | Action::Suspend | Action::Quit => "General",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|action| action.get_name() == name).copied()
+    }
+}
+
+const CATEGORIES: [&str; 7] = ["Movement", "Time travel", "Notes", "Search", "General", "Visual mode", "Command line"];
+
+/// Bindings are looked up separately for each mode
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeyMode {
+    Normal,
+    Visual,
+    Command,
+}
+
+impl KeyMode {
+    /// Config variables for a mode are the action name with this prefix, like visual_cancel
+    pub fn get_prefix(&self) -> &'static str {
+        match self {
+            KeyMode::Normal => "",
+            KeyMode::Visual => "visual_",
+            KeyMode::Command => "command_",
+        }
+    }
+}
+
+/// A mouse button or wheel with the modifiers held down
+#[derive(Clone, Copy, PartialEq)]
+pub struct Gesture {
+    pub button: MouseButton,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl Gesture {
+    pub fn new(button: MouseButton) -> Self {
+        Gesture { button, ctrl: false, alt: false, shift: false }
+    }
+
+    /// Termion can't parse presses with modifiers so they come as raw bytes like \x1b[<16;10;5M
+    pub fn parse(bytes: &[u8]) -> Option<(Gesture, u16, u16)> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        let text = text.strip_prefix("\x1b[<")?.strip_suffix('M')?;
+        let mut numbers = text.split(';').map(|number| number.parse::<u16>());
+        let (code, x, y) = (numbers.next()?.ok()?, numbers.next()?.ok()?, numbers.next()?.ok()?);
+        let button = match code & 0b1100_0011 {
+            0 => MouseButton::Left,
+            1 => MouseButton::Middle,
+            2 => MouseButton::Right,
+            64 => MouseButton::WheelUp,
+            65 => MouseButton::WheelDown,
+            _ => return None,
+        };
+        // Bit 32 is a drag which is not a gesture
+        if code & 32 != 0 { return None; }
+        let gesture = Gesture { button, shift: code & 4 != 0, alt: code & 8 != 0, ctrl: code & 16 != 0 };
+        Some((gesture, x, y))
+    }
+
+    /// Names like wheel_up, middle_click or ctrl+alt+left_click
+    pub fn from_name(name: &str) -> Option<Gesture> {
+        let name = name.to_lowercase();
+        let mut parts: Vec<&str> = name.split('+').collect();
+        let button = match parts.pop()? {
+            "left_click" | "leftclick" => MouseButton::Left,
+            "middle_click" | "middleclick" => MouseButton::Middle,
+            "right_click" | "rightclick" => MouseButton::Right,
+            "wheel_up" | "wheelup" => MouseButton::WheelUp,
+            "wheel_down" | "wheeldown" => MouseButton::WheelDown,
+            _ => return None,
+        };
+        let mut gesture = Gesture::new(button);
+        for part in parts {
+            match part {
+                "ctrl" | "control" => gesture.ctrl = true,
+                "alt" | "meta" => gesture.alt = true,
+                "shift" => gesture.shift = true,
+                _ => return None,
+            }
+        }
+        Some(gesture)
+    }
+
+    pub fn get_name(&self) -> String {
+        let button = match self.button {
+            MouseButton::Left => "left_click",
+            MouseButton::Middle => "middle_click",
+            MouseButton::Right => "right_click",
+            MouseButton::WheelUp => "wheel_up",
+            MouseButton::WheelDown => "wheel_down",
+        };
+        let mut name = String::new();
+        if self.ctrl { name += "ctrl+"; }
+        if self.alt { name += "alt+"; }
+        if self.shift { name += "shift+"; }
+        name + button
+    }
+
+    pub fn is_click(&self) -> bool {
+        !matches!(self.button, MouseButton::WheelUp | MouseButton::WheelDown)
+    }
+}
+
+/// A key or mouse gesture a binding is made of
+#[derive(Clone, PartialEq)]
+pub enum Input {
+    Keys(Vec<Key>),
+    Mouse(Gesture),
+}
+
+impl Input {
+    pub fn get_name(&self) -> String {
+        match self {
+            Input::Keys(keys) => keys.iter().map(|key| get_key_name(*key)).collect(),
+            Input::Mouse(gesture) => gesture.get_name(),
+        }
+    }
+}
+
+/// The same names the config file takes, so a binding can be copied from a message
+pub fn get_key_name(key: Key) -> String {
+    match key {
+        Key::Char('\n') => String::from("<enter>"),
+        Key::Char('\t') => String::from("<tab>"),
+        Key::Char(' ') => String::from("<space>"),
+        Key::Char(',') => String::from("<comma>"),
+        Key::Char(char) => char.to_string(),
+        Key::Ctrl(char) => format!("ctrl({})", char),
+        Key::Alt(char) => format!("alt({})", char),
+        Key::F(number) => format!("f({})", number),
+        Key::Backspace => String::from("<backspace>"),
+        Key::Left => String::from("<left>"),
+        Key::Right => String::from("<right>"),
+        Key::Up => String::from("<up>"),
+        Key::Down => String::from("<down>"),
+        Key::Home => String::from("<home>"),
+        Key::End => String::from("<end>"),
+        Key::PageUp => String::from("<pageup>"),
+        Key::PageDown => String::from("<pagedown>"),
+        Key::BackTab => String::from("<backtab>"),
+        Key::Delete => String::from("<delete>"),
+        Key::Insert => String::from("<insert>"),
+        Key::Esc => String::from("<esc>"),
+        _ => String::from("<null>"),
+    }
+}
+
+#[derive(Clone)]
+struct Binding {
+    mode: KeyMode,
+    input: Input,
+    action: Action,
+}
+
+/// The bindings of every mode, the defaults until config.txt changes them
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    pub timeout: Duration,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        let mut keymap = Keymap { bindings: Vec::new(), timeout: Duration::from_millis(1000) };
+        let normal = [
+            (Action::Quit, vec![Key::Char('q')]),
+            (Action::Edit, vec![Key::Char('\n')]),
+            (Action::Up, vec![Key::Char('w')]),
+            (Action::Left, vec![Key::Char('a')]),
+            (Action::Down, vec![Key::Char('s')]),
+            (Action::Right, vec![Key::Char('d')]),
+            (Action::CalendarUp, vec![Key::Char('W')]),
+            (Action::CalendarLeft, vec![Key::Char('A')]),
+            (Action::CalendarDown, vec![Key::Char('S')]),
+            (Action::CalendarRight, vec![Key::Char('D')]),
+            (Action::GoBackTime, vec![Key::Left]),
+            (Action::GoForwardTime, vec![Key::Right]),
+            (Action::GoBackCalendar, vec![Key::Down]),
+            (Action::GoForwardCalendar, vec![Key::Up]),
+            (Action::Today, vec![Key::Char('g'), Key::Char('g')]),
+            (Action::Search, vec![Key::Char('/')]),
+            (Action::NextMatch, vec![Key::Char('n')]),
+            (Action::PreviousMatch, vec![Key::Char('N')]),
+            (Action::GoToDate, vec![Key::Char('g'), Key::Char('t')]),
+            (Action::CommandLine, vec![Key::Char(':')]),
+            (Action::Visual, vec![Key::Char('v')]),
+            (Action::Undo, vec![Key::Char('u')]),
+            (Action::Redo, vec![Key::Ctrl('r')]),
+            (Action::Yank, vec![Key::Char('y')]),
+            (Action::Cut, vec![Key::Char('x')]),
+            (Action::Paste, vec![Key::Char('p')]),
+            (Action::Register, vec![Key::Char('"')]),
+            (Action::Help, vec![Key::Char('?')]),
+            (Action::Suspend, vec![Key::Ctrl('z')]),
+        ];
+        for (action, keys) in normal {
+            keymap.bind(KeyMode::Normal, action, vec![Input::Keys(keys)]);
+        }
+        let mouse = [
+            (Action::GoBackTime, Gesture::new(MouseButton::WheelUp)),
+            (Action::GoForwardTime, Gesture::new(MouseButton::WheelDown)),
+            (Action::GoBackCalendar, Gesture { ctrl: true, ..Gesture::new(MouseButton::WheelUp) }),
+            (Action::GoForwardCalendar, Gesture { ctrl: true, ..Gesture::new(MouseButton::WheelDown) }),
+            (Action::Paste, Gesture::new(MouseButton::Middle)),
+        ];
+        for (action, gesture) in mouse {
+            keymap.add(KeyMode::Normal, action, Input::Mouse(gesture));
+        }
+        keymap.bind(KeyMode::Visual, Action::Cancel, vec![Input::Keys(vec![Key::Esc])]);
+        let command = [
+            (Action::Submit, vec![Key::Char('\n')]),
+            (Action::Cancel, vec![Key::Esc]),
+            (Action::Complete, vec![Key::Char('\t')]),
+            (Action::HistoryBack, vec![Key::Up]),
+            (Action::HistoryForward, vec![Key::Down]),
+            (Action::Delete, vec![Key::Backspace]),
+        ];
+        for (action, keys) in command {
+            keymap.bind(KeyMode::Command, action, vec![Input::Keys(keys)]);
+        }
+        keymap
+    }
+
+    /// Replaces every binding the action had in the mode
+    pub fn bind(&mut self, mode: KeyMode, action: Action, inputs: Vec<Input>) {
+        self.bindings.retain(|binding| binding.mode != mode || binding.action != action);
+        for input in inputs {
+            self.add(mode, action, input);
+        }
+    }
+
+    fn add(&mut self, mode: KeyMode, action: Action, input: Input) {
+        self.bindings.push(Binding { mode, input, action });
+    }
+
+    // Visual mode falls back on the normal mode bindings
+    fn get_bindings(&self, mode: KeyMode) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |binding| {
+            binding.mode == mode || (mode == KeyMode::Visual && binding.mode == KeyMode::Normal)
+        })
+    }
+
+    fn find_input(&self, mode: KeyMode, input: &Input) -> Option<Action> {
+        // Bindings of the mode itself come before the fallback ones
+        let mut found = None;
+        for binding in self.get_bindings(mode) {
+            if binding.input != *input { continue; }
+            if binding.mode == mode { return Some(binding.action); }
+            found = Some(binding.action);
+        }
+        found
+    }
+
+    pub fn find(&self, mode: KeyMode, keys: &[Key]) -> Option<Action> {
+        self.find_input(mode, &Input::Keys(keys.to_vec()))
+    }
+
+    pub fn find_mouse(&self, mode: KeyMode, gesture: Gesture) -> Option<Action> {
+        self.find_input(mode, &Input::Mouse(gesture))
+    }
+
+    fn has_longer(&self, mode: KeyMode, keys: &[Key]) -> bool {
+        self.get_bindings(mode).any(|binding| match &binding.input {
+            Input::Keys(binding_keys) => binding_keys.len() > keys.len() && binding_keys.starts_with(keys),
+            Input::Mouse(_) => false,
+        })
+    }
+
+    fn get_input_names(&self, mode: KeyMode, action: Action) -> String {
+        let names: Vec<String> = self.bindings.iter()
+        .filter(|binding| binding.mode == mode && binding.action == action)
+        .map(|binding| binding.input.get_name())
+        .collect();
+        if names.is_empty() { String::from("(unbound)") } else { names.join(", ") }
+    }
+
+    /// Lines of the help overlay, every action with its bindings under its category
+    pub fn get_help(&self) -> Vec<String> {
+        let mut sections = Vec::new();
+        for category in CATEGORIES.iter() {
+            let mode = match *category {
+                "Visual mode" => KeyMode::Visual,
+                "Command line" => KeyMode::Command,
+                _ => KeyMode::Normal,
+            };
+            // Visual mode only lists what it binds itself, the rest comes from normal mode
+            let lines: Vec<(String, &str)> = ACTIONS.iter()
+            .filter(|action| match mode {
+                KeyMode::Visual => self.bindings.iter().any(|binding| binding.mode == mode && binding.action == **action),
+                _ => action.get_category() == *category,
+            })
+            .map(|action| (self.get_input_names(mode, *action), action.get_description()))
+            .collect();
+            sections.push((category, lines));
+        }
+        let width = sections.iter().flat_map(|(_, lines)| lines.iter())
+        .map(|(names, _)| names.chars().count()).max().unwrap_or(0);
+        let mut help = vec![String::from("Type a number before a key to repeat it, like 5d")];
+        for (category, lines) in sections {
+            if lines.is_empty() { continue; }
+            help.push(String::new());
+            help.push(category.to_string());
+            for (names, description) in lines {
+                help.push(format!("  {:width$}  {}", names, description, width = width));
+            }
+        }
+        help
+    }
+
+    /// Every input bound to more than one action in the same mode
+    pub fn get_conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, binding) in self.bindings.iter().enumerate() {
+            let others: Vec<&str> = self.bindings.iter().skip(i + 1)
+            .filter(|other| other.mode == binding.mode && other.input == binding.input && other.action != binding.action)
+            .map(|other| other.action.get_name())
+            .collect();
+            // Only the first binding of an input reports it
+            let reported = self.bindings.iter().take(i)
+            .any(|other| other.mode == binding.mode && other.input == binding.input);
+            if others.is_empty() || reported { continue; }
+            let mode = match binding.mode {
+                KeyMode::Normal => "",
+                KeyMode::Visual => " in visual mode",
+                KeyMode::Command => " in command mode",
+            };
+            conflicts.push(format!("{} is bound to {} and {}{}",
+                binding.input.get_name(), binding.action.get_name(), others.join(" and "), mode));
+        }
+        conflicts
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new()
+    }
+}
+
+/// What a key pushed onto a KeyState turned into
+pub enum KeyResult {
+    Action(Action, usize),
+    Pending,
+    Unbound,
+}
+
+/// Keys typed so far for a sequence like gg or 5d
+pub struct KeyState {
+    pending: Vec<Key>,
+    count: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+impl KeyState {
+    pub fn new() -> Self {
+        KeyState { pending: Vec::new(), count: None, deadline: None }
+    }
+
+    pub fn push(&mut self, key: Key, keymap: &Keymap, mode: KeyMode) -> KeyResult {
+        if self.pending.is_empty() {
+            if let Key::Char(char) = key {
+                if let Some(digit) = char.to_digit(10) {
+                    // A leading 0 is not a count so it can still be bound
+                    if digit != 0 || self.count.is_some() {
+                        let count = (self.count.unwrap_or(0) * 10 + digit as usize).min(MAX_COUNT);
+                        self.count = Some(count);
+                        self.deadline = None;
+                        return KeyResult::Pending;
+                    }
+                }
+            }
+        }
+        self.pending.push(key);
+        let action = keymap.find(mode, &self.pending);
+        if keymap.has_longer(mode, &self.pending) {
+            // Wait for the next key, on timeout the shorter binding runs if there is one
+            self.deadline = Some(Instant::now() + keymap.timeout);
+            return KeyResult::Pending;
+        }
+        let count = self.count.unwrap_or(1);
+        self.clear();
+        match action {
+            Some(action) => KeyResult::Action(action, count),
+            None => KeyResult::Unbound,
+        }
+    }
+
+    pub fn get_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn check_timeout(&mut self, keymap: &Keymap, mode: KeyMode) -> Option<(Action, usize)> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => (),
+            _ => return None,
+        }
+        let action = keymap.find(mode, &self.pending);
+        let count = self.count.unwrap_or(1);
+        self.clear();
+        action.map(|action| (action, count))
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.count = None;
+        self.deadline = None;
+    }
+}
+
+impl Default for KeyState {
+    fn default() -> Self {
+        KeyState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use termion::event::Key;
+
+    use super::{Action, Input, KeyMode, KeyResult, KeyState, Keymap};
+
+    fn push_all(state: &mut KeyState, keymap: &Keymap, keys: &[Key]) -> KeyResult {
+        let mut result = KeyResult::Unbound;
+        for key in keys {
+            result = state.push(*key, keymap, KeyMode::Normal);
+        }
+        result
+    }
+
+    #[test]
+    fn resolves_a_sequence() {
+        let keymap = Keymap::new();
+        let mut state = KeyState::new();
+        assert!(matches!(state.push(Key::Char('g'), &keymap, KeyMode::Normal), KeyResult::Pending));
+        assert!(state.get_deadline().is_some());
+        assert!(matches!(state.push(Key::Char('t'), &keymap, KeyMode::Normal), KeyResult::Action(Action::GoToDate, 1)));
+        assert!(state.get_deadline().is_none());
+        assert!(matches!(push_all(&mut state, &keymap, &[Key::Char('g'), Key::Char('x')]), KeyResult::Unbound));
+    }
+
+    #[test]
+    fn times_out_on_a_prefix() {
+        let mut keymap = Keymap::new();
+        keymap.timeout = Duration::from_millis(0);
+        let mut state = KeyState::new();
+        // Nothing is bound to g alone so the sequence is dropped
+        assert!(matches!(state.push(Key::Char('g'), &keymap, KeyMode::Normal), KeyResult::Pending));
+        assert_eq!(state.check_timeout(&keymap, KeyMode::Normal).map(|(_, count)| count), None);
+        assert!(state.get_deadline().is_none());
+        // Once it is, the shorter binding runs with the count typed before it
+        keymap.bind(KeyMode::Normal, Action::Help, vec![Input::Keys(vec![Key::Char('g')])]);
+        assert!(matches!(push_all(&mut state, &keymap, &[Key::Char('3'), Key::Char('g')]), KeyResult::Pending));
+        assert!(matches!(state.check_timeout(&keymap, KeyMode::Normal), Some((Action::Help, 3))));
+        assert!(state.check_timeout(&keymap, KeyMode::Normal).is_none());
+    }
+
+    #[test]
+    fn counts_the_digits_before_an_action() {
+        let keymap = Keymap::new();
+        let mut state = KeyState::new();
+        assert!(matches!(push_all(&mut state, &keymap, &[Key::Char('5'), Key::Char('d')]), KeyResult::Action(Action::Right, 5)));
+        assert!(matches!(push_all(&mut state, &keymap, &[Key::Char('1'), Key::Char('0'), Key::Char('s')]), KeyResult::Action(Action::Down, 10)));
+        assert!(matches!(push_all(&mut state, &keymap, &[Key::Char('9'); 8]), KeyResult::Pending));
+        assert!(matches!(state.push(Key::Char('a'), &keymap, KeyMode::Normal), KeyResult::Action(Action::Left, 9999)));
+        // Without a count the action runs once
+        assert!(matches!(state.push(Key::Char('w'), &keymap, KeyMode::Normal), KeyResult::Action(Action::Up, 1)));
+    }
+
+    #[test]
+    fn reports_overlapping_bindings() {
+        let mut keymap = Keymap::new();
+        assert!(keymap.get_conflicts().is_empty());
+        keymap.bind(KeyMode::Normal, Action::Up, vec![Input::Keys(vec![Key::Char('w')]), Input::Keys(vec![Key::Char('s')])]);
+        keymap.bind(KeyMode::Command, Action::Cancel, vec![Input::Keys(vec![Key::Char('\n')])]);
+        assert_eq!(keymap.get_conflicts(), vec![
+            String::from("s is bound to down and up"),
+            String::from("<enter> is bound to submit and cancel in command mode"),
+        ]);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::position::Position;
+
+pub const CALENDAR_WIDTH: u16 = 22;
+pub const CALENDAR_HEIGHT: u16 = 13;
+// Compact calendars skip the empty row between weeks
+pub const COMPACT_HEIGHT: u16 = 8;
+const GAP_X: u16 = 2;
+const GAP_Y: u16 = 1;
+
+// Where the calendars go for a terminal size
+#[derive(Clone, Copy)]
+pub struct Layout {
+    pub columns: usize,
+    pub rows: usize,
+    pub compact: bool,
+    start: Position,
+    step_x: u16,
+    step_y: u16,
+}
+
+impl Layout {
+    // None when not even one compact calendar fits
+    pub fn new(bounds: Position) -> Option<Layout> {
+        let width = bounds.get_x();
+        let height = bounds.get_y().saturating_sub(1); // Last row is the status bar
+        let columns = (width + GAP_X) / (CALENDAR_WIDTH + GAP_X);
+        let rows = (height + GAP_Y) / (CALENDAR_HEIGHT + GAP_Y);
+        if columns > 0 && rows > 0 {
+            let (x, step_x) = spread(width, columns, CALENDAR_WIDTH, GAP_X);
+            let (y, step_y) = spread(height, rows, CALENDAR_HEIGHT, GAP_Y);
+            return Some(Layout {
+                columns: columns as usize,
+                rows: rows as usize,
+                compact: false,
+                start: Position::new(x, y),
+                step_x,
+                step_y,
+            });
+        }
+        if width < CALENDAR_WIDTH || height < COMPACT_HEIGHT { return None; }
+        let (x, _) = spread(width, 1, CALENDAR_WIDTH, GAP_X);
+        let (y, _) = spread(height, 1, COMPACT_HEIGHT, GAP_Y);
+        Some(Layout { columns: 1, rows: 1, compact: true, start: Position::new(x, y), step_x: 0, step_y: 0 })
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    // Lines from one row of calendars to the next, 0 when there is only room for one
+    pub fn get_step_y(&self) -> u16 {
+        self.step_y
+    }
+
+    // Top left corner of the calendar at index, calendars go left to right then down
+    pub fn get_position(&self, index: usize) -> Position {
+        let column = (index % self.columns) as u16;
+        let row = (index / self.columns) as u16;
+        Position::new(self.start.get_x() + column * self.step_x, self.start.get_y() + row * self.step_y)
+    }
+}
+
+// Share the spare room between the gaps and both margins, returns the first offset and the step
+fn spread(room: u16, count: u16, size: u16, gap: u16) -> (u16, u16) {
+    let spare = room - (count * size + (count - 1) * gap);
+    let extra = spare / (count + 1);
+    let margin = extra + (spare - extra * (count + 1)) / 2;
+    (1 + margin, size + gap + extra)
+}
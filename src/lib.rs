@@ -0,0 +1,8 @@
+//! The parts of terminal-calendar that don't need a terminal: month grids, the note store and
+//! the config file with its key bindings. The `terminal-calendar` binary draws its TUI on top.
+
+pub mod config;
+pub mod error;
+pub mod keymap;
+pub mod month;
+pub mod notes;
@@ -0,0 +1,127 @@
+use termion::color::AnsiValue;
+
+use crate::{config::Config, position::{Direction, Position}, terminal::Formatter, tui::Widget};
+
+// Scrollable list of lines drawn as a box in the middle of the screen
+pub struct ListBox {
+    title: String,
+    items: Vec<String>,
+    pub selected: usize,
+    scroll: usize,
+    start: Position,
+    end: Position,
+    bg_color: AnsiValue,
+    fg_color: AnsiValue,
+    select_color: AnsiValue,
+}
+
+impl ListBox {
+    pub fn new(title: String, items: Vec<String>, bounds: Position, config: &Config) -> Self {
+        let longest = items.iter().map(|item| item.chars().count()).max().unwrap_or(0);
+        let longest = longest.max(title.chars().count()) as u16 + 2;
+        let width = longest.min(bounds.get_x().saturating_sub(4)).max(1);
+        let height = (items.len() as u16 + 1).min(bounds.get_y().saturating_sub(4)).max(2);
+        let x = (bounds.get_x().saturating_sub(width)) / 2 + 1;
+        let y = (bounds.get_y().saturating_sub(height)) / 2 + 1;
+        ListBox {
+            title,
+            items,
+            selected: 0,
+            scroll: 0,
+            start: Position::new(x, y),
+            end: Position::new(x + width - 1, y + height - 1),
+            bg_color: config.list_bg_color,
+            fg_color: config.list_text_color,
+            select_color: config.list_select_color,
+        }
+    }
+
+    // Put the top left corner at position while keeping the box on screen
+    pub fn move_to(&mut self, position: Position, bounds: Position) {
+        let width = self.end.get_x() - self.start.get_x();
+        let height = self.end.get_y() - self.start.get_y();
+        let x = position.get_x().min(bounds.get_x().saturating_sub(width)).max(1);
+        let y = position.get_y().min(bounds.get_y().saturating_sub(height)).max(1);
+        self.start = Position::new(x, y);
+        self.end = Position::new(x + width, y + height);
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn visible_rows(&self) -> usize {
+        // First row is the title
+        (self.end.get_y() - self.start.get_y()) as usize
+    }
+
+    pub fn move_selection(&mut self, direction: Direction) -> Formatter {
+        if self.items.is_empty() { return Formatter::new(); }
+        let selected = match direction {
+            Direction::Up | Direction::Left => self.selected.saturating_sub(1),
+            Direction::Down | Direction::Right => (self.selected + 1).min(self.items.len() - 1),
+        };
+        self.select(selected)
+    }
+
+    pub fn move_page(&mut self, direction: Direction) -> Formatter {
+        if self.items.is_empty() { return Formatter::new(); }
+        let rows = self.visible_rows();
+        let selected = match direction {
+            Direction::Up | Direction::Left => self.selected.saturating_sub(rows),
+            Direction::Down | Direction::Right => (self.selected + rows).min(self.items.len() - 1),
+        };
+        self.select(selected)
+    }
+
+    pub fn select(&mut self, index: usize) -> Formatter {
+        if index >= self.items.len() { return Formatter::new(); }
+        self.selected = index;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + self.visible_rows() {
+            self.scroll = self.selected + 1 - self.visible_rows();
+        }
+        self.draw_format()
+    }
+
+    pub fn get_hovered_item(&self, position: Position) -> Option<usize> {
+        if !self.is_hovered(position) || position.get_y() == self.start.get_y() { return None; }
+        let index = self.scroll + (position.get_y() - self.start.get_y() - 1) as usize;
+        if index < self.items.len() { Some(index) } else { None }
+    }
+
+    fn fit(&self, text: &str) -> String {
+        let width = (self.end.get_x() - self.start.get_x()) as usize;
+        let text: String = text.chars().take(width).collect();
+        format!(" {:width$}", text, width = width)
+    }
+}
+
+impl Widget for ListBox {
+    fn draw_format(&mut self) -> Formatter {
+        let mut format = Formatter::new()
+        .create_box(&self.start, &self.end, &self.bg_color)
+        .go_to(self.start)
+        .fg_color(&self.fg_color)
+        .text(self.fit(&self.title));
+        let rows = self.visible_rows();
+        for (row, (index, item)) in self.items.iter().enumerate().skip(self.scroll).take(rows).enumerate() {
+            let color = if index == self.selected { &self.select_color } else { &self.bg_color };
+            format += &Formatter::new()
+            .go_to(Position::new(self.start.get_x(), self.start.get_y() + 1 + row as u16))
+            .bg_color(color)
+            .fg_color(&self.fg_color)
+            .text(self.fit(item));
+        }
+        format
+    }
+
+    fn get_start(&self) -> Position {
+        self.start
+    }
+
+    fn get_end(&self) -> Position {
+        self.end
+    }
+}
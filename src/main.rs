@@ -1,11 +1,86 @@
 mod terminal;
+mod buffer;
 mod position;
 mod tui;
-mod config;
 mod calendar;
+mod prompt;
+mod list;
+mod search;
+mod dates;
+mod commands;
+mod undo;
+mod status;
+mod layout;
+mod events;
+mod cache;
+mod session;
+mod headless;
 
-use crate::tui::Tui;
+use std::{env, io::{self, Write}, process};
+
+use termion::input::TermRead;
+// The library half, imported here so the TUI modules reach it through crate:: like their own
+use terminal_calendar::{config, error, keymap, month, notes};
+
+use crate::{config::Config, headless::VirtualBackend, notes::Notes, position::Position, session::Session, tui::Tui};
+
+const USAGE: &str = "Usage: terminal-calendar [--fresh | --snapshot WIDTHxHEIGHT]
+
+  --fresh                  Start at this month with empty histories instead of the last session
+  --snapshot WIDTHxHEIGHT  Play the keys from stdin on a virtual terminal of that size and print
+                           the screen, without saving anything. Handy for checking a layout:
+                           printf 'dd/dentist\\n' | terminal-calendar --snapshot 100x30
+  --help                   Show this";
 
 fn main() {
-    Tui::new().start();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args.as_slice() {
+        [] => run(Some(Session::load())),
+        // Starts at this month with empty histories
+        ["--fresh"] => run(None),
+        ["--help"] | ["-h"] => println!("{}", USAGE),
+        ["--snapshot"] => snapshot("80x24"),
+        ["--snapshot", size] => snapshot(size),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        },
+    }
+}
+
+fn run(session: Option<Session>) {
+    terminal::install_panic_hook();
+    if let Err(error) = Tui::new().and_then(|mut tui| tui.start(session)) {
+        eprintln!("terminal-calendar: {}", error);
+        process::exit(1);
+    }
+}
+
+// Plays the keys from stdin on a virtual terminal of the given size and prints the screen
+// Like: printf 'dd/dentist\n' | terminal-calendar --snapshot 100x30
+fn snapshot(size: &str) {
+    let size = 
+    match size.split_once('x').and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?))) {
+        Some((x, y)) => Position::new(x, y),
+        None => {
+            eprintln!("terminal-calendar: size should look like 80x24");
+            process::exit(2);
+        },
+    };
+    let backend = VirtualBackend::new(size);
+    let screen = backend.clone();
+    let events = io::stdin().events().filter_map(|event| event.ok());
+    let result = Config::get_config()
+    .and_then(|config| Tui::with_backend(Box::new(backend), config, Notes::load_read_only()))
+    .and_then(|mut tui| tui.run_script(tui::get_this_month(), events));
+    if let Err(error) = result {
+        eprintln!("terminal-calendar: {}", error);
+        process::exit(1);
+    }
+    let mut stdout = io::stdout();
+    for line in screen.get_lines() {
+        // Stop quietly when the output is piped into something like head
+        if writeln!(stdout, "{}", line).is_err() { break; }
+    }
 }
\ No newline at end of file
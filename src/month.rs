@@ -0,0 +1,235 @@
+//! Months laid out as whole weeks, without anything to do with drawing them.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday, naive::{MAX_DATE, MIN_DATE}};
+
+/// One cell of a [`Month`] grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Day {
+    pub date: NaiveDate,
+    /// False for the days of the months before and after that fill up the first and last week.
+    pub in_month: bool,
+}
+
+/// Seven days in a row, starting on the week start of the [`Month`] it came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Week {
+    /// ISO 8601 week number of the Thursday in the row, so weeks that don't start on Monday
+    /// still get the number most calendars print next to them.
+    pub number: u32,
+    pub days: [Day; 7],
+}
+
+/// A month of a year with the weekday its weeks start on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Month {
+    first: NaiveDate,
+    week_start: Weekday,
+}
+
+impl Month {
+    /// `None` when the year and month don't make a date chrono can hold, or when the weeks of
+    /// the month would run past the first or last date it can.
+    pub fn new(year: i32, month: u32, week_start: Weekday) -> Option<Month> {
+        Month::from_first(NaiveDate::from_ymd_opt(year, month, 1)?, week_start)
+    }
+
+    /// The month a date falls in, `None` for the same months [`Month::new`] leaves out.
+    pub fn containing(date: NaiveDate, week_start: Weekday) -> Option<Month> {
+        Month::from_first(date.with_day(1)?, week_start)
+    }
+
+    /// The first month whose weeks can all be laid out.
+    pub fn earliest(week_start: Weekday) -> Month {
+        let first = MIN_DATE.with_month(2).unwrap_or(MIN_DATE);
+        Month { first, week_start }
+    }
+
+    /// The last month whose weeks can all be laid out.
+    pub fn latest(week_start: Weekday) -> Month {
+        let first = MAX_DATE.with_day(1).and_then(|date| date.with_month(11)).unwrap_or(MAX_DATE);
+        Month { first, week_start }
+    }
+
+    // The days before and after a month only ever fill up the rest of a week
+    fn from_first(first: NaiveDate, week_start: Weekday) -> Option<Month> {
+        first.checked_sub_signed(Duration::days(6))?;
+        get_last_day(first)?.checked_add_signed(Duration::days(6))?;
+        Some(Month { first, week_start })
+    }
+
+    pub fn get_year(&self) -> i32 {
+        self.first.year()
+    }
+
+    /// From 1 for January to 12 for December.
+    pub fn get_month(&self) -> u32 {
+        self.first.month()
+    }
+
+    pub fn get_week_start(&self) -> Weekday {
+        self.week_start
+    }
+
+    pub fn get_first_day(&self) -> NaiveDate {
+        self.first
+    }
+
+    pub fn get_last_day(&self) -> NaiveDate {
+        get_last_day(self.first).unwrap_or(self.first)
+    }
+
+    pub fn get_days_in_month(&self) -> u32 {
+        self.get_last_day().day()
+    }
+
+    /// The month after this one, `None` after [`Month::latest`].
+    pub fn next(&self) -> Option<Month> {
+        Month::from_first(self.get_last_day().succ_opt()?, self.week_start)
+    }
+
+    /// The month before this one, `None` before [`Month::earliest`].
+    pub fn previous(&self) -> Option<Month> {
+        Month::from_first(self.first.pred_opt()?.with_day(1)?, self.week_start)
+    }
+
+    /// Every week with a day of the month in it, the first and last are filled up with days
+    /// of the months around it.
+    pub fn get_weeks(&self) -> Vec<Week> {
+        let last = self.get_last_day();
+        let mut weeks = Vec::with_capacity(6);
+        let mut start = self.first.checked_sub_signed(Duration::days(get_column(self.first, self.week_start) as i64));
+        while let Some(row_start) = start.filter(|start| *start <= last) {
+            let week = 
+            match self.get_week(row_start) {
+                Some(week) => week,
+                None => break,
+            };
+            weeks.push(week);
+            start = row_start.checked_add_signed(Duration::days(7));
+        }
+        weeks
+    }
+
+    fn get_week(&self, start: NaiveDate) -> Option<Week> {
+        let mut days = [Day { date: start, in_month: false }; 7];
+        for (column, day) in days.iter_mut().enumerate() {
+            let date = start.checked_add_signed(Duration::days(column as i64))?;
+            *day = Day { date, in_month: date.year() == self.get_year() && date.month() == self.get_month() };
+        }
+        let thursday = days.iter().find(|day| day.date.weekday() == Weekday::Thu).map_or(start, |day| day.date);
+        Some(Week { number: thursday.iso_week().week(), days })
+    }
+
+    /// Row and column of a date of this month in [`Month::get_weeks`].
+    pub fn find(&self, date: NaiveDate) -> Option<(usize, usize)> {
+        if date < self.first || date > self.get_last_day() { return None; }
+        let offset = get_column(self.first, self.week_start) + (date.day() - 1) as usize;
+        Some((offset / 7, offset % 7))
+    }
+}
+
+fn get_last_day(first: NaiveDate) -> Option<NaiveDate> {
+    let (year, month) = if first.month() == 12 { (first.year().checked_add(1)?, 1) } else { (first.year(), first.month() + 1) };
+    NaiveDate::from_ymd_opt(year, month, 1)?.pred_opt()
+}
+
+/// Column a date goes in when weeks start on `week_start`, from 0 to 6.
+pub fn get_column(date: NaiveDate, week_start: Weekday) -> usize {
+    ((date.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7) as usize
+}
+
+/// The weekdays in column order for weeks that start on `week_start`.
+pub fn get_weekdays(week_start: Weekday) -> [Weekday; 7] {
+    let mut weekdays = [week_start; 7];
+    for index in 1..7 {
+        weekdays[index] = weekdays[index - 1].succ();
+    }
+    weekdays
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+
+    use super::Month;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    #[test]
+    fn fills_the_first_and_last_week_with_other_months() {
+        let weeks = Month::new(2027, 3, Weekday::Sun).unwrap().get_weeks();
+        assert_eq!(weeks.len(), 5);
+        let first = weeks[0].days;
+        assert_eq!(first[0].date, date(2027, 2, 28));
+        assert!(!first[0].in_month);
+        assert_eq!(first[1].date, date(2027, 3, 1));
+        assert!(first[1].in_month);
+        let last = weeks[4].days;
+        assert_eq!(last[3].date, date(2027, 3, 31));
+        assert!(last[3].in_month);
+        assert_eq!(last[6].date, date(2027, 4, 3));
+        assert!(!last[6].in_month);
+    }
+
+    #[test]
+    fn takes_six_weeks_when_the_month_starts_late() {
+        // May 2027 starts on a Saturday and has 31 days
+        let weeks = Month::new(2027, 5, Weekday::Sun).unwrap().get_weeks();
+        assert_eq!(weeks.len(), 6);
+        assert_eq!(weeks[0].days[6].date, date(2027, 5, 1));
+        assert_eq!(weeks[5].days[1].date, date(2027, 5, 31));
+    }
+
+    #[test]
+    fn starts_weeks_on_monday() {
+        let month = Month::new(2027, 3, Weekday::Mon).unwrap();
+        let weeks = month.get_weeks();
+        assert_eq!(weeks.len(), 5);
+        assert_eq!(weeks[0].days[0].date, date(2027, 3, 1));
+        assert!(weeks[0].days.iter().all(|day| day.in_month));
+        assert_eq!(weeks[4].days[6].date, date(2027, 4, 4));
+        assert_eq!(super::get_weekdays(Weekday::Mon)[6], Weekday::Sun);
+    }
+
+    #[test]
+    fn numbers_weeks_by_their_thursday() {
+        let weeks = Month::new(2027, 3, Weekday::Sun).unwrap().get_weeks();
+        assert_eq!(weeks[0].number, 9);
+        assert_eq!(weeks[4].number, 13);
+        // The first row of January 2027 is still the last week of 2026
+        let weeks = Month::new(2027, 1, Weekday::Mon).unwrap().get_weeks();
+        assert_eq!(weeks[0].days[0].date, date(2026, 12, 28));
+        assert_eq!(weeks[0].number, 53);
+        assert_eq!(weeks[1].number, 1);
+    }
+
+    #[test]
+    fn finds_dates_on_the_grid() {
+        let month = Month::new(2027, 3, Weekday::Sun).unwrap();
+        assert_eq!(month.find(date(2027, 3, 1)), Some((0, 1)));
+        assert_eq!(month.find(date(2027, 3, 31)), Some((4, 3)));
+        assert_eq!(month.find(date(2027, 2, 28)), None);
+        assert_eq!(month.find(date(2027, 4, 1)), None);
+        let weeks = month.get_weeks();
+        for day in weeks.iter().flat_map(|week| week.days.iter()).filter(|day| day.in_month) {
+            let (row, column) = month.find(day.date).unwrap();
+            assert_eq!(weeks[row].days[column], *day);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_months_chrono_can_lay_out() {
+        assert!(Month::new(2027, 13, Weekday::Sun).is_none());
+        assert!(Month::new(262143, 12, Weekday::Sun).is_none());
+        assert!(Month::new(-262144, 1, Weekday::Sun).is_none());
+        let latest = Month::latest(Weekday::Sun);
+        assert!(latest.next().is_none());
+        assert_eq!(latest.get_last_day(), date(262143, 11, 30));
+        assert!(latest.get_weeks().iter().flat_map(|week| week.days.iter()).any(|day| day.date == latest.get_last_day()));
+        let earliest = Month::earliest(Weekday::Sun);
+        assert!(earliest.previous().is_none());
+        assert_eq!(earliest.get_weeks()[0].days.iter().find(|day| day.in_month).unwrap().date, earliest.get_first_day());
+    }
+}
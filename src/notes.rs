@@ -0,0 +1,167 @@
+//! The note store, one note of text per date.
+
+use std::{collections::BTreeMap, fs::{self, File}, io::{self, BufRead, BufReader, Write}, path::{Path, PathBuf}, time::SystemTime};
+
+use chrono::NaiveDate;
+
+use crate::config;
+
+/// Notes are stored one per line as "YYYY-MM-DD=text" with newlines escaped
+pub struct Notes {
+    notes: BTreeMap<NaiveDate, String>,
+    file: Option<PathBuf>, // None keeps changes in memory only
+    written: Option<SystemTime>, // Modified time of the file after our own last save
+}
+
+impl Notes {
+    /// No notes, kept in memory only
+    pub fn new() -> Self {
+        Notes { notes: BTreeMap::new(), file: None, written: None }
+    }
+
+    /// The notes saved in a file, or none when it doesn't exist yet. Changes are saved back to it
+    pub fn from_path(path: &Path) -> Self {
+        let mut notes = Notes {
+            notes: BTreeMap::new(),
+            file: Some(path.to_path_buf()),
+            written: None,
+        };
+        let _ = notes.import(path);
+        notes
+    }
+
+    /// The saved notes, or none when there are none yet
+    pub fn load() -> Self {
+        Notes::from_path(&config::get_path("notes.txt"))
+    }
+
+    /// The saved notes, but changes are never written back
+    pub fn load_read_only() -> Self {
+        let mut notes = Notes::load();
+        notes.file = None;
+        notes
+    }
+
+    /// Writes the notes back unless they were loaded read only
+    pub fn save(&mut self) {
+        // TODO let the user know when this fails
+        if let Some(file) = &self.file {
+            let _ = self.export(file);
+            self.written = get_modified(file);
+        }
+    }
+
+    /// True when something other than save wrote the file since it was last saved
+    pub fn is_changed_on_disk(&self) -> bool {
+        match &self.file {
+            Some(file) => get_modified(file) != self.written,
+            None => false,
+        }
+    }
+
+    /// Writes every note to a file in the same format they are saved in
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        for (date, note) in self.notes.iter() {
+            text += &format!("{}={}\n", date.format("%Y-%m-%d"), escape(note));
+        }
+        File::create(path)?.write_all(text.as_bytes())
+    }
+
+    /// Reads notes from a file, replacing the notes of any date found in it
+    pub fn import(&mut self, path: &Path) -> io::Result<usize> {
+        let mut count = 0;
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let split_index = match line.find('=') {
+                Some(index) => index,
+                None => continue,
+            };
+            let (date, text) = line.split_at(split_index);
+            if let Ok(date) = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+                self.set(date, unescape(&text[1..]));
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// The note of a date, empty when it has none
+    pub fn get(&self, date: NaiveDate) -> &str {
+        match self.notes.get(&date) {
+            Some(note) => note,
+            None => "",
+        }
+    }
+
+    /// Replaces the note of a date, blank text removes it
+    pub fn set(&mut self, date: NaiveDate, text: String) {
+        if text.trim().is_empty() {
+            self.notes.remove(&date);
+        } else {
+            self.notes.insert(date, text);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NaiveDate, &String)> {
+        self.notes.iter()
+    }
+}
+
+impl Default for Notes {
+    fn default() -> Self {
+        Notes::new()
+    }
+}
+
+fn get_modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Escapes newlines and backslashes so text fits on one line
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Undoes escape
+pub fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(char) => result.push(char),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::{self, File}, time::{Duration, SystemTime}};
+
+    use chrono::NaiveDate;
+
+    use super::Notes;
+
+    #[test]
+    fn tells_its_own_saves_from_other_writes() {
+        let path = env::temp_dir().join(format!("terminal-calendar-notes-{}.txt", std::process::id()));
+        let mut notes = Notes::from_path(&path);
+        notes.set(NaiveDate::from_ymd(2027, 3, 14), String::from("dentist"));
+        notes.save();
+        assert!(!notes.is_changed_on_disk());
+        // Another program writing the file moves its modified time
+        let later = SystemTime::now() + Duration::from_secs(60);
+        File::options().write(true).open(&path).and_then(|file| file.set_modified(later)).unwrap();
+        assert!(notes.is_changed_on_disk());
+        assert_eq!(Notes::from_path(&path).get(NaiveDate::from_ymd(2027, 3, 14)), "dentist");
+        fs::remove_file(&path).unwrap();
+        assert!(!Notes::new().is_changed_on_disk());
+    }
+}
@@ -1,5 +1,3 @@
-use crate::terminal::Terminal;
-
 #[derive(Clone, Copy)]
 pub struct Position {
     x: u16,
@@ -23,11 +21,6 @@ impl Position {
         Position::new(1, 1)
     }
 
-    pub fn new_center() -> Self {
-        let bound = Terminal::get_boundaries();
-        Position::new(bound.get_x() / 2, bound.get_y() / 2)
-    }
-
     pub fn get_x(&self) -> u16 {
         self.x
     }
@@ -36,32 +29,16 @@ impl Position {
         self.y
     }
 
-    pub fn set_x(&mut self, x: u16) -> bool {
-        self.set(x, self.y)
-    }
-
-    pub fn set_y(&mut self, y: u16) -> bool {
-        self.set(self.x, y)
+    pub fn set(&mut self, x: u16, y: u16) {
+        self.x = x; self.y = y;
     }
 
-    pub fn set(&mut self, x: u16, y: u16) -> bool {
-        if self.respect_boundary(x, y) {
-            self.x = x; self.y = y;
-            true
-        } else { false }
-    }
-
-    fn respect_boundary(&self, x: u16, y: u16) -> bool {
-        // Check current and future positions
-        self.is_in_boundary() && Position::new(x, y).is_in_boundary()
-    }
-
-    pub fn is_in_boundary(&self) -> bool {
-        let bounds = &Terminal::get_boundaries();
-        self.x > 0 && self.y > 0 && self.get_x() <= bounds.get_x() + 1 && self.get_y() <= bounds.get_y() + 1
+    pub fn offset(&self, x: i32, y: i32) -> Self {
+        Position::new((self.x as i32 + x).max(0) as u16, (self.y as i32 + y).max(0) as u16)
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum Direction {
     Up,
     Down,
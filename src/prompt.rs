@@ -0,0 +1,150 @@
+use termion::{color::AnsiValue, event::Key};
+
+use crate::{config::Config, keymap::Action, position::Position, terminal::Formatter};
+
+pub enum PromptAction {
+    Submit(String),
+    Cancel,
+    Changed,
+    None,
+}
+
+// Single line text input drawn on the bottom row of the screen
+pub struct Prompt {
+    prefix: String,
+    text: String,
+    error: Option<String>,
+    position: Position,
+    width: u16,
+    bg_color: AnsiValue,
+    fg_color: AnsiValue,
+    error_color: AnsiValue,
+    history: Vec<String>,
+    history_index: usize,
+    draft: String,
+    completer: Option<fn(&str) -> Vec<String>>,
+    completions: Vec<String>,
+    completion_index: usize,
+}
+
+impl Prompt {
+    pub fn new(prefix: &str, bounds: Position, config: &Config) -> Self {
+        Prompt {
+            prefix: prefix.to_string(),
+            text: String::new(),
+            error: None,
+            position: Position::new(1, bounds.get_y()),
+            width: bounds.get_x(),
+            bg_color: config.prompt_bg_color,
+            fg_color: config.prompt_text_color,
+            error_color: config.prompt_error_color,
+            history: Vec::new(),
+            history_index: 0,
+            draft: String::new(),
+            completer: None,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+
+    pub fn set_history(&mut self, history: &[String]) {
+        self.history = history.to_vec();
+        self.history_index = self.history.len();
+    }
+
+    pub fn set_completer(&mut self, completer: fn(&str) -> Vec<String>) {
+        self.completer = Some(completer);
+    }
+
+    // Action is what the key is bound to in the command keymap, other keys are typed
+    pub fn handle_key(&mut self, key: Key, action: Option<Action>) -> PromptAction {
+        match action {
+            Some(Action::Submit) => return PromptAction::Submit(self.text.clone()),
+            Some(Action::Cancel) => return PromptAction::Cancel,
+            Some(Action::Complete) => {
+                if !self.complete() { return PromptAction::None; }
+                self.error = None;
+                return PromptAction::Changed;
+            },
+            Some(Action::HistoryBack) => {
+                if self.history_index == 0 { return PromptAction::None; }
+                if self.history_index == self.history.len() { self.draft = self.text.clone(); }
+                self.history_index -= 1;
+                self.text = self.history[self.history_index].clone();
+            },
+            Some(Action::HistoryForward) => {
+                if self.history_index >= self.history.len() { return PromptAction::None; }
+                self.history_index += 1;
+                self.text = match self.history.get(self.history_index) {
+                    Some(text) => text.clone(),
+                    None => self.draft.clone(),
+                };
+            },
+            Some(Action::Delete) => {
+                if self.text.pop().is_none() { return PromptAction::Cancel; }
+            },
+            _ => match key {
+                Key::Char(char) if !char.is_control() => self.text.push(char),
+                _ => return PromptAction::None,
+            },
+        }
+        self.completions.clear();
+        self.error = None;
+        PromptAction::Changed
+    }
+
+    // Tab goes through every completion of the text typed before the first tab
+    fn complete(&mut self) -> bool {
+        let completer = 
+        match self.completer {
+            Some(completer) => completer,
+            None => return false,
+        };
+        if self.completions.is_empty() {
+            self.completions = completer(&self.text);
+            self.completion_index = 0;
+        } else {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+        }
+        match self.completions.get(self.completion_index) {
+            Some(text) => {
+                self.text = text.clone();
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn draw_format(&self) -> Formatter {
+        let end = Position::new(self.position.get_x() + self.width - 1, self.position.get_y());
+        let mut text = format!("{}{}", self.prefix, self.text);
+        let max = self.width as usize - 1;
+        if text.chars().count() > max {
+            // Keep the end of the text visible while typing
+            text = text.chars().skip(text.chars().count() - max).collect();
+        }
+        let mut format = Formatter::new()
+        .create_box(&self.position, &end, &self.bg_color)
+        .go_to(self.position)
+        .fg_color(&self.fg_color)
+        .text(text)
+        .bg_color(&self.fg_color)
+        .text(String::from(" "));
+        if let Some(error) = &self.error {
+            // Room left after the text, the cursor and a space
+            let length = self.prefix.chars().count() + self.text.chars().count() + 2;
+            if length < max {
+                let error: String = error.chars().take(max - length).collect();
+                format = format
+                .bg_color(&self.bg_color)
+                .fg_color(&self.error_color)
+                .text(format!(" {}", error));
+            }
+        }
+        format
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::NaiveDate;
+
+use crate::notes::Notes;
+
+pub struct Match {
+    pub date: NaiveDate,
+    pub snippet: String,
+}
+
+pub struct Search {
+    pub query: String,
+    pub matches: Vec<Match>,
+    current: usize,
+}
+
+impl Search {
+    pub fn new(query: String, notes: &Notes) -> Self {
+        let lowercase = query.to_lowercase();
+        let mut matches = Vec::new();
+        for (date, note) in notes.iter() {
+            // Only the first matching line of a note is used as the snippet
+            let line = note.lines().find(|line| line.to_lowercase().contains(&lowercase));
+            if let Some(line) = line {
+                matches.push(Match { date: *date, snippet: line.trim().to_string() });
+            }
+        }
+        Search { query, matches, current: 0 }
+    }
+
+    pub fn get_items(&self) -> Vec<String> {
+        self.matches.iter()
+        .map(|found| format!("{}  {}", found.date.format("%Y-%m-%d"), found.snippet))
+        .collect()
+    }
+
+    pub fn select(&mut self, index: usize) -> Option<NaiveDate> {
+        let found = self.matches.get(index)?;
+        self.current = index;
+        Some(found.date)
+    }
+
+    pub fn next(&mut self) -> Option<NaiveDate> {
+        if self.matches.is_empty() { return None; }
+        self.select((self.current + 1) % self.matches.len())
+    }
+
+    pub fn previous(&mut self) -> Option<NaiveDate> {
+        if self.matches.is_empty() { return None; }
+        self.select((self.current + self.matches.len() - 1) % self.matches.len())
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fs::{self, File}, io::{self, Write}};
+
+use chrono::NaiveDate;
+
+use crate::{config, notes};
+
+// Only the latest entries of each history are kept between runs
+const HISTORY_LIMIT: usize = 100;
+
+// Where the last run left off, stored as "name=value" lines in session.txt
+#[derive(Default)]
+pub struct Session {
+    pub month: Option<NaiveDate>,
+    pub selected: Option<NaiveDate>,
+    pub scroll_mode: Option<bool>,
+    pub search_history: Vec<String>,
+    pub command_history: Vec<String>,
+}
+
+impl Session {
+    // A missing or broken file is just an empty session
+    pub fn load() -> Self {
+        let mut session = Session::default();
+        let text = 
+        match fs::read_to_string(config::get_path("session.txt")) {
+            Ok(text) => text,
+            Err(_) => return session,
+        };
+        for line in text.lines() {
+            let (name, value) = 
+            match line.split_once('=') {
+                Some(split) => split,
+                None => continue,
+            };
+            match name {
+                "month" => session.month = parse_date(value),
+                "selected" => session.selected = parse_date(value),
+                "view" => session.scroll_mode = match value {
+                    "scroll" => Some(true),
+                    "pages" => Some(false),
+                    _ => None,
+                },
+                "search" => session.search_history.push(notes::unescape(value)),
+                "command" => session.command_history.push(notes::unescape(value)),
+                _ => (),
+            }
+        }
+        session
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = String::new();
+        if let Some(month) = self.month { text += &format!("month={}\n", month.format("%Y-%m-%d")); }
+        if let Some(date) = self.selected { text += &format!("selected={}\n", date.format("%Y-%m-%d")); }
+        if let Some(scroll_mode) = self.scroll_mode {
+            text += &format!("view={}\n", if scroll_mode { "scroll" } else { "pages" });
+        }
+        for (name, history) in [("search", &self.search_history), ("command", &self.command_history)].iter() {
+            for entry in history.iter().skip(history.len().saturating_sub(HISTORY_LIMIT)) {
+                text += &format!("{}={}\n", name, notes::escape(entry));
+            }
+        }
+        File::create(config::get_path("session.txt"))?.write_all(text.as_bytes())
+    }
+}
+
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()
+}
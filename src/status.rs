@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, NaiveDate};
+use termion::color::AnsiValue;
+
+use crate::{config::Config, position::Position, terminal::Formatter, tui::Widget};
+
+struct Message {
+    text: String,
+    error: bool,
+    time: Instant,
+}
+
+// Bottom row showing the selected date, the mode and the last message
+pub struct StatusBar {
+    position: Position,
+    width: u16,
+    bg_color: AnsiValue,
+    fg_color: AnsiValue,
+    error_color: AnsiValue,
+    timeout: Duration,
+    date: Option<NaiveDate>,
+    notes: usize,
+    mode: &'static str,
+    message: Option<Message>,
+    changed: bool,
+}
+
+impl StatusBar {
+    pub fn new(bounds: Position, config: &Config) -> Self {
+        StatusBar {
+            position: Position::new(1, bounds.get_y()),
+            width: bounds.get_x(),
+            bg_color: config.status_bg_color,
+            fg_color: config.status_text_color,
+            error_color: config.status_error_color,
+            timeout: Duration::from_millis(config.status_message_ms as u64),
+            date: None,
+            notes: 0,
+            mode: "",
+            message: None,
+            changed: true,
+        }
+    }
+
+    // Keeps the message when the bar is rebuilt for a new size or config
+    pub fn replace(&mut self, mut status: StatusBar) {
+        status.message = self.message.take();
+        *self = status;
+    }
+
+    pub fn set_context(&mut self, date: Option<NaiveDate>, notes: usize, mode: &'static str) {
+        if self.date != date || self.notes != notes || self.mode != mode {
+            self.date = date;
+            self.notes = notes;
+            self.mode = mode;
+            self.changed = true;
+        }
+    }
+
+    pub fn set_message(&mut self, text: String) {
+        self.message = Some(Message { text, error: false, time: Instant::now() });
+        self.changed = true;
+    }
+
+    pub fn set_error(&mut self, text: String) {
+        self.message = Some(Message { text, error: true, time: Instant::now() });
+        self.changed = true;
+    }
+
+    // When the message should be cleared
+    pub fn get_deadline(&self) -> Option<Instant> {
+        self.message.as_ref().map(|message| message.time + self.timeout)
+    }
+
+    // True once when something shown has changed since the last draw
+    pub fn needs_draw(&mut self) -> bool {
+        if let Some(message) = &self.message {
+            if message.time.elapsed() >= self.timeout {
+                self.message = None;
+                self.changed = true;
+            }
+        }
+        self.changed
+    }
+
+    fn get_context(&self) -> String {
+        let mut text = format!(" {}", self.mode);
+        if let Some(date) = self.date {
+            let days = if NaiveDate::from_ymd_opt(date.year(), 2, 29).is_some() { 366 } else { 365 };
+            text += &format!(" | {} | Day {} of {}", date.format("%A %-d %B %Y"), date.ordinal(), days);
+        }
+        let plural = if self.notes == 1 { "" } else { "s" };
+        text + &format!(" | {} note{} ", self.notes, plural)
+    }
+}
+
+impl Widget for StatusBar {
+    fn draw_format(&mut self) -> Formatter {
+        self.changed = false;
+        let end = self.get_end();
+        let width = self.width as usize;
+        let context: String = self.get_context().chars().take(width).collect();
+        let mut format = Formatter::new()
+        .create_box(&self.position, &end, &self.bg_color)
+        .go_to(self.position)
+        .fg_color(&self.fg_color)
+        .text(context.clone());
+        if let Some(message) = &self.message {
+            // Messages go on the right and get cut to the room left by the context
+            let room = width.saturating_sub(context.chars().count() + 1);
+            let text: String = message.text.chars().take(room).collect();
+            if !text.is_empty() {
+                let x = self.position.get_x() + (width - text.chars().count() - 1) as u16;
+                let color = if message.error { &self.error_color } else { &self.fg_color };
+                format = format
+                .go_to(Position::new(x, self.position.get_y()))
+                .fg_color(color)
+                .text(text);
+            }
+        }
+        format
+    }
+
+    fn get_start(&self) -> Position {
+        self.position
+    }
+
+    fn get_end(&self) -> Position {
+        Position::new(self.position.get_x() + self.width - 1, self.position.get_y())
+    }
+}
@@ -1,52 +1,144 @@
-use std::{fs::File, io::Write, ops::{Add, AddAssign}};
+use std::{fs::File, io::{self, Write}, mem, ops::{Add, AddAssign}, os::unix::io::AsRawFd, panic};
 
-use termion::{clear, color::{self, AnsiValue}, cursor, input::{Events, MouseTerminal, TermRead}, raw::{IntoRawMode, RawTerminal}, screen, style};
+use termion::{clear, color::{self, AnsiValue}, cursor, event::Event, input::{MouseTerminal, TermRead}, raw::{IntoRawMode, RawTerminal}, screen, style};
 
-use crate::position::Position;
+use crate::{buffer::Buffer, error::{Error, Result}, position::Position};
 
-pub struct Terminal {
+pub type EventSource = Box<dyn Iterator<Item = io::Result<Event>> + Send>;
+
+// Where the escape codes go, a real tty or something that pretends to be one
+pub trait Backend {
+    fn write(&mut self, text: &str);
+    fn get_size(&self) -> Result<Position>;
+    fn set_raw(&mut self, raw: bool) -> Result<()>;
+    fn set_mouse(&mut self, mouse: bool) -> Result<()>;
+    // None when events are fed in some other way
+    fn get_events(&self) -> Option<EventSource>;
+}
+
+pub struct TtyBackend {
     terminal: File,
     raw: Option<RawTerminal<File>>,
     mouse_terminal: Option<MouseTerminal<File>>,
 }
 
-impl Terminal {
-    pub fn new_raw() -> Self {
-        let mut terminal = Terminal::new();
-        terminal.raw = Some(Terminal::get_terminal().into_raw_mode().unwrap());
-        terminal.raw.as_ref().unwrap().activate_raw_mode().unwrap();
-        terminal
-    }
-
-    pub fn new() -> Self {
-        Terminal {
-            terminal: Terminal::get_terminal(),
+impl TtyBackend {
+    pub fn new() -> Result<Self> {
+        Ok(TtyBackend {
+            terminal: TtyBackend::get_terminal()?,
             raw: None,
             mouse_terminal: None,
+        })
+    }
+
+    fn get_terminal() -> Result<File> {
+        termion::get_tty().map_err(Error::NoTty)
+    }
+}
+
+impl Backend for TtyBackend {
+    fn write(&mut self, text: &str) {
+        // The terminal can be gone after a hangup, nothing useful to do about it
+        let _ = write!(self.terminal, "{}", text);
+        let _ = self.terminal.flush();
+    }
+
+    fn get_size(&self) -> Result<Position> {
+        let (x, y) = termion::terminal_size().map_err(Error::TerminalSize)?;
+        Ok(Position::new(x, y))
+    }
+
+    fn set_raw(&mut self, raw: bool) -> Result<()> {
+        match (&self.raw, raw) {
+            (Some(terminal), true) => terminal.activate_raw_mode().map_err(Error::RawMode),
+            (Some(terminal), false) => terminal.suspend_raw_mode().map_err(Error::RawMode),
+            (None, true) => {
+                self.raw = Some(TtyBackend::get_terminal()?.into_raw_mode().map_err(Error::RawMode)?);
+                Ok(())
+            },
+            (None, false) => Ok(()),
         }
     }
 
-    pub fn get_events() -> Events<File>{
-        termion::get_tty().unwrap().events()
+    fn set_mouse(&mut self, mouse: bool) -> Result<()> {
+        // The mouse terminal turns mouse reporting off again when dropped
+        if !mouse {
+            self.mouse_terminal = None;
+        } else if self.mouse_terminal.is_none() {
+            self.mouse_terminal = Some(MouseTerminal::from(TtyBackend::get_terminal()?));
+        }
+        Ok(())
     }
 
-    fn get_terminal() -> File {
-        termion::get_tty().unwrap()
+    fn get_events(&self) -> Option<EventSource> {
+        let terminal = TtyBackend::get_terminal().ok()?;
+        Some(Box::new(terminal.events()))
     }
+}
 
-    pub fn mouse_terminal(&mut self) {
-        if let None = self.mouse_terminal {
-            self.mouse_terminal = Some(MouseTerminal::from(Terminal::get_terminal()));
+// A panic would leave raw mode, the alternate screen and mouse reporting on so the message
+// ends up unreadable, put the terminal back the way it was before printing it
+pub fn install_panic_hook() {
+    let tty = 
+    match termion::get_tty() {
+        Ok(tty) => tty,
+        Err(_) => return,
+    };
+    let mut original: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut original) } != 0 { return; }
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut tty) = termion::get_tty() {
+            let _ = write!(
+                tty,
+                "\x1b[?1000l\x1b[?1002l\x1b[?1015l\x1b[?1006l{}{}{}",
+                style::Reset,
+                cursor::Show,
+                screen::ToMainScreen
+            );
+            let _ = tty.flush();
+            unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &original); }
         }
+        hook(info);
+    }));
+}
+
+pub struct Terminal {
+    backend: Box<dyn Backend>,
+    screen: Buffer, // What is on the terminal right now
+    frame: Buffer, // What should be on it after the next write
+}
+
+impl Terminal {
+    pub fn new(backend: Box<dyn Backend>) -> Result<Self> {
+        let buffer = Buffer::new(backend.get_size()?);
+        Ok(Terminal {
+            backend,
+            screen: buffer.clone(),
+            frame: buffer,
+        })
+    }
+
+    pub fn get_events(&self) -> Option<EventSource> {
+        self.backend.get_events()
     }
 
     pub fn write(&mut self, message: String) {
-        write!(self.terminal, "{}", message).unwrap();
-        self.terminal.flush().unwrap();
+        self.backend.write(&message);
     }
 
     pub fn clear_all(&mut self) {
         self.write(format!("{}{}", clear::All, cursor::Goto::default()));
+        self.screen.clear();
+        self.frame.clear();
+    }
+
+    // Start over with empty buffers when the size changed, the terminal may have moved things around
+    pub fn resize(&mut self, bounds: Position) {
+        if self.frame.get_size() == bounds { return; }
+        self.screen = Buffer::new(bounds);
+        self.frame = Buffer::new(bounds);
+        self.write(format!("{}{}{}", color::Bg(color::Reset), clear::All, cursor::Goto::default()));
     }
 
     pub fn reset(&mut self) {
@@ -54,71 +146,111 @@ impl Terminal {
         self.clear_all();
     }
 
-    pub fn begin(&mut self) {
+    pub fn begin(&mut self) -> Result<()> {
+        self.backend.set_raw(true)?;
+        self.backend.set_mouse(true)?;
         self.write(format!(
             "{}{}{}",
             screen::ToAlternateScreen,
             clear::All,
             cursor::Hide
         ));
+        self.screen.clear();
+        self.frame.clear();
+        Ok(())
     }
 
-    pub fn exit(&mut self) {
+    pub fn exit(&mut self) -> Result<()> {
         self.write(format!("{}{}", cursor::Show, screen::ToMainScreen));
-        if let Some(raw) = &self.raw { raw.suspend_raw_mode().unwrap(); }
+        self.backend.set_mouse(false)?;
+        self.backend.set_raw(false)
+    }
+
+    // OSC 52 asks the terminal to put text on the system clipboard, terminals without it ignore it
+    pub fn copy_to_clipboard(&mut self, text: &str) {
+        self.write(format!("\x1b]52;c;{}\x07", base64(text.as_bytes())));
     }
 
-    pub fn get_boundaries() -> Position {
-        let (x, y) = termion::terminal_size().unwrap();
-        Position::new(x, y)
+    pub fn get_boundaries(&self) -> Result<Position> {
+        self.backend.get_size()
     }
 
     pub fn write_format(&mut self, format: Formatter) {
-        if format.string.is_empty() { return; }
-        self.write(format.string);
+        if format.ops.is_empty() { return; }
+        format.apply(&mut self.frame);
+        let output = self.frame.diff(&self.screen);
+        self.screen = self.frame.clone();
+        if !output.is_empty() { self.write(output); }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(TABLE[(value >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
     }
+    text
 }
 
+#[derive(Clone)]
+enum Op {
+    GoTo(u16, u16),
+    Bg(u8),
+    Fg(u8),
+    Text(String),
+}
+
+// Drawing steps that get applied to the frame buffer when written
 pub struct Formatter {
-    string: String,
+    ops: Vec<Op>,
 }
 
 impl Add<&Formatter> for Formatter {
     type Output = Formatter;
     fn add(mut self, other: &Formatter) -> Self {
-        self.string += &other.string;
+        self.ops.extend(other.ops.iter().cloned());
         self
     }
 }
 
 impl AddAssign<&Formatter> for Formatter {
     fn add_assign(&mut self, other: &Formatter) {
-        self.string += &other.string;
+        self.ops.extend(other.ops.iter().cloned());
     }
 }
 
 impl Formatter {
     pub fn new() -> Self {
-        Formatter { string: String::new() }
+        Formatter { ops: Vec::new() }
     }
 
     pub fn bg_color(mut self, color: &AnsiValue) -> Self {
-        self.string += &color::Bg(*color).to_string();
+        self.ops.push(Op::Bg(color.0));
         self
     }
 
     pub fn fg_color(mut self, color: &AnsiValue) -> Self {
-        self.string += &color::Fg(*color).to_string();
+        self.ops.push(Op::Fg(color.0));
         self
     }
 
     pub fn go_to(mut self, position: Position) -> Self {
-        self.string += &cursor::Goto(position.get_x(), position.get_y()).to_string();
+        self.ops.push(Op::GoTo(position.get_x(), position.get_y()));
         self
     }
 
     pub fn text(mut self, text: String) -> Self {
-        self.string += &text;
+        self.ops.push(Op::Text(text));
         self
     }
 
@@ -135,4 +267,26 @@ impl Formatter {
         }
         self
     }
-}
\ No newline at end of file
+
+    // Move everything up by lines, rows that end up above the screen or past last_row are dropped
+    pub fn scroll(mut self, lines: u16, last_row: u16) -> Self {
+        for op in self.ops.iter_mut() {
+            if let Op::GoTo(_, y) = op {
+                // Row 0 is off screen so the buffer ignores the text
+                *y = if *y <= lines || *y - lines > last_row { 0 } else { *y - lines };
+            }
+        }
+        self
+    }
+
+    fn apply(&self, buffer: &mut Buffer) {
+        for op in self.ops.iter() {
+            match op {
+                Op::GoTo(x, y) => buffer.go_to(*x, *y),
+                Op::Bg(color) => buffer.set_bg(Some(*color)),
+                Op::Fg(color) => buffer.set_fg(Some(*color)),
+                Op::Text(text) => buffer.write(text),
+            }
+        }
+    }
+}
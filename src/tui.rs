@@ -1,37 +1,149 @@
-use std::{sync::{Arc, Mutex, mpsc::{Receiver, Sender, channel}}, thread};
+use std::{collections::HashMap, convert::TryFrom, path::Path, sync::{Arc, Mutex, mpsc::{Receiver, RecvTimeoutError, Sender, channel}}, thread, time::{Duration, Instant}};
 
-use chrono::{Date, Datelike, Local};
+use chrono::{Date, Datelike, Local, NaiveDate, TimeZone, Weekday};
 use termion::{color::AnsiValue, event::{Event, Key, MouseButton, MouseEvent}};
 
-use crate::{calendar::Calendar, config::Config, position::{Direction, Position}, terminal::{Formatter, Terminal}};
+use crate::{cache::MonthCache, calendar::Calendar, error::{Error, Result}, commands::{self, Command}, config::{self, Config}, dates, events::{self, TuiEvent}, keymap::{Action, Gesture, KeyMode, KeyResult, KeyState}, layout::{self, Layout}, list::ListBox, month::Month, notes::Notes, position::{Direction, Position}, prompt::{Prompt, PromptAction}, search::Search, session::Session, status::StatusBar, terminal::{Backend, EventSource, Formatter, Terminal, TtyBackend}, undo::UndoHistory};
+
+enum Mode {
+    Normal,
+    Search,
+    SearchResults,
+    GoTo,
+    Command,
+    Menu,
+    AddEvent,
+    Visual,
+    Info,
+}
+
+impl Mode {
+    fn get_name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Search | Mode::SearchResults => "SEARCH",
+            Mode::GoTo => "GO TO",
+            Mode::Command => "COMMAND",
+            Mode::Menu => "MENU",
+            Mode::AddEvent => "ADD EVENT",
+            Mode::Visual => "VISUAL",
+            Mode::Info => "INFO",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum MenuAction {
+    Edit,
+    Clear,
+    Copy,
+    Paste,
+    AddEvent,
+    CountDays,
+}
+
+impl MenuAction {
+    fn get_name(&self, range: bool) -> &'static str {
+        match self {
+            MenuAction::Edit => "Edit note",
+            MenuAction::Clear => if range { "Clear notes" } else { "Clear note" },
+            MenuAction::Copy => "Copy note",
+            MenuAction::Paste => "Paste note",
+            MenuAction::AddEvent => "Add event",
+            MenuAction::CountDays => "Count days",
+        }
+    }
+}
 
 pub struct Tui {
     bounds: Position,
     config: Config,
     terminal: Terminal,
+    events: Option<EventSource>,
+    interactive: bool, // False when there is no tty to hand over to an editor
     calendars: Vec<Calendar>,
-    tx_mut: Arc<Mutex<Sender<Event>>>,
-    rx: Receiver<Event>,
+    tx_mut: Arc<Mutex<Sender<TuiEvent>>>,
+    rx: Receiver<TuiEvent>,
     quit: bool,
+    notes: Notes,
+    mode: Mode,
+    prompt: Option<Prompt>,
+    list: Option<ListBox>,
+    search: Option<Search>,
+    search_history: Vec<String>,
+    command_history: Vec<String>,
+    overrides: Vec<(String, String)>,
+    menu: Vec<MenuAction>,
+    registers: HashMap<char, String>,
+    register: Option<char>,
+    awaiting_register: bool,
+    last_click: Option<(Instant, usize, usize)>,
+    range: Option<(NaiveDate, NaiveDate)>,
+    drag_start: Option<NaiveDate>,
+    undo: UndoHistory,
+    keys: KeyState,
+    status: StatusBar,
+    layout: Option<Layout>,
+    months: MonthCache,
+    start: Date<Local>, // First month shown, kept even while the terminal is too small for any
+    scroll: u16, // Lines the first row of months is scrolled past the top in scroll mode
+    today: Date<Local>,
+    error: Option<Error>, // What made it quit early, handed back by start
 }
 
 impl Tui {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self> {
+        Tui::with_backend(Box::new(TtyBackend::new()?), Config::get_config()?, Notes::load())
+    }
+
+    pub fn with_backend(backend: Box<dyn Backend>, config: Config, notes: Notes) -> Result<Self> {
         let (tx, rx) = channel();
-        Tui {
-            bounds: Terminal::get_boundaries(),
-            config: Config::get_config(),
-            terminal: Terminal::new_raw(),
+        let undo = UndoHistory::new(config.undo_limit);
+        let terminal = Terminal::new(backend)?;
+        let events = terminal.get_events();
+        let bounds = terminal.get_boundaries()?;
+        let status = StatusBar::new(bounds, &config);
+        Ok(Tui {
+            bounds,
+            config,
+            interactive: events.is_some(),
+            events,
+            terminal,
             calendars: Vec::new(),
             tx_mut: Arc::new(Mutex::new(tx)), 
             rx,
             quit: false,
-        }
+            notes,
+            mode: Mode::Normal,
+            prompt: None,
+            list: None,
+            search: None,
+            search_history: Vec::new(),
+            command_history: Vec::new(),
+            overrides: Vec::new(),
+            menu: Vec::new(),
+            registers: HashMap::new(),
+            register: None,
+            awaiting_register: false,
+            last_click: None,
+            range: None,
+            drag_start: None,
+            undo,
+            keys: KeyState::new(),
+            status,
+            layout: None,
+            months: MonthCache::new(),
+            start: get_this_month(),
+            scroll: 0,
+            today: Local::today(),
+            error: None,
+        })
     }
 
-    pub fn start(&mut self) {
-        self.terminal.begin();
-        self.tui_loop();
+    // Picks up where the session left off, or at this month without one
+    pub fn start(&mut self, session: Option<Session>) -> Result<()> {
+        self.terminal.begin()?;
+        self.tui_loop(session)
     }
 
     fn init(&mut self, date: Date<Local>) {
@@ -40,190 +152,1016 @@ impl Tui {
         self.terminal.write_format(format);
     }
 
-    fn tui_loop(&mut self) {
-        self.init(Local::today().with_day(1).unwrap());
-        self.terminal.mouse_terminal();
-        let tx = self.tx_mut.clone();
-        thread::spawn(move || {
-            for key in Terminal::get_events() {
-                // I use lock to stop this thread when I need to edit
-                // If I let it keep sending it causes lag for editor input
-                match tx.try_lock(){
-                    Ok(tx) => tx.send(key.unwrap()).unwrap(),
-                    Err(_) => {
-                        // Wait until lock is ok and do nothing in that time
-                        let _lock = tx.lock().unwrap(); 
-                    }, 
-                }
-            }
-        });
+    // Runs events one after another without waiting starting at month, then leaves the screen as it is
+    pub fn run_script(&mut self, month: Date<Local>, events: impl Iterator<Item = Event>) -> Result<()> {
+        self.terminal.begin()?;
+        self.init(month);
+        self.report_conflicts();
+        let mut calendar_index: usize = 0;
+        self.tick(&mut calendar_index);
+        for event in events {
+            if self.quit { break; }
+            self.handle_event(TuiEvent::Input(event), &mut calendar_index);
+            self.tick(&mut calendar_index);
+        }
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    fn tui_loop(&mut self, session: Option<Session>) -> Result<()> {
         let mut calendar_index: usize = 0;
+        match session {
+            Some(session) => self.restore(session, &mut calendar_index),
+            None => self.init(get_this_month()),
+        }
+        self.report_conflicts();
+        self.tick(&mut calendar_index);
+        if let Some(events) = self.events.take() {
+            events::spawn_input(self.tx_mut.clone(), events);
+        }
+        let tx = self.tx_mut.lock().unwrap().clone();
+        events::spawn_signals(tx.clone());
+        events::spawn_file_watch(tx);
         while !self.quit {
-            self.handle_event(&mut calendar_index);
-            if self.bounds != Terminal::get_boundaries() {
-                calendar_index = 0;
-                self.reset(Local::today().with_day(1).unwrap());
+            // Sleep until an event comes in or the next timer is due
+            let timeout = self.get_next_deadline().saturating_duration_since(Instant::now());
+            match self.rx.recv_timeout(timeout) {
+                Ok(event) => self.handle_event(event, &mut calendar_index),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
             }
+            self.tick(&mut calendar_index);
+        }
+        let exit = self.terminal.exit();
+        // Also reached after SIGTERM or SIGHUP so make sure nothing is lost
+        self.notes.save();
+        if let Err(error) = self.get_session(calendar_index).save() {
+            // The terminal is back to normal by now so this shows up after the calendar closes
+            eprintln!("terminal-calendar: could not save the session: {}", error);
         }
-        self.terminal.exit();
+        self.error.take().map_or(exit, Err)
     }
 
-    fn handle_event(&mut self, index: &mut usize) {
-        if let Ok(event) = self.rx.try_recv() {
-            match event {
-                Event::Key(key) => self.handle_key(key, index),
-                Event::Mouse(mouse) => self.handle_mouse(mouse, index),
-                Event::Unsupported(_) => (),
-            };
+    // Stops the loop, the error ends up in front of the user once the terminal is back to normal
+    fn fail(&mut self, error: Error) {
+        if self.error.is_none() { self.error = Some(error); }
+        self.quit = true;
+    }
+
+    fn restore(&mut self, session: Session, index: &mut usize) {
+        self.search_history = session.search_history;
+        self.command_history = session.command_history;
+        if let Some(scroll_mode) = session.scroll_mode {
+            if scroll_mode != self.config.scroll_mode {
+                let value = scroll_mode.to_string();
+                self.config.set("scroll_mode", &value);
+                self.overrides.push((String::from("scroll_mode"), value));
+            }
+        }
+        let month = session.month.and_then(|month| month.with_day(1)).map(|month| to_local(month, Local::today()));
+        self.init(month.unwrap_or_else(get_this_month));
+        let (next, button) = 
+        match session.selected.and_then(|date| self.find_visible(date)) {
+            Some(found) => found,
+            None => return,
+        };
+        *index = next;
+        if let Some(calendar) = self.calendars.get_mut(next) {
+            let format = calendar.select_button(&self.config, button);
+            self.terminal.write_format(format);
         }
     }
 
-    fn handle_key(&mut self, key: Key, index: &mut usize) {
-        let config = &self.config;
-        if key == config.quit {
-            self.quit = true;
-        } else if key == config.edit {
-           self.edit(index);
-        } else if key == config.go_back_time {
-            self.reset(self.time_travel(Direction::Left));
-        } else if key == config.go_forward_time {
-            self.reset(self.time_travel(Direction::Right));
-        } else if key == config.go_back_calendar {
-            self.reset(self.time_travel(Direction::Down));
-        } else if key == config.go_forward_calendar {
-            self.reset(self.time_travel(Direction::Up));
-        } else {
-            let format =
-            if key == config.left {
-                self.calendars.get_mut(*index).unwrap()
-                .move_cursor(&config, Direction::Left)
-            } else if key == config.right {
-                self.calendars.get_mut(*index).unwrap()
-                .move_cursor(&config, Direction::Right)
-            } else if key == config.up {
-                self.calendars.get_mut(*index).unwrap()
-                .move_cursor(&config, Direction::Up)
-            } else if key == config.down {
-                self.calendars.get_mut(*index).unwrap()
-                .move_cursor(&config, Direction::Down)
-            } else if key == config.calendar_left {
-                self.move_calendar(index, Direction::Left)
-            } else if key == config.calendar_right {
-                self.move_calendar(index, Direction::Right)
-            } else if key == config.calendar_up {
-                self.move_calendar(index, Direction::Up)
-            } else if key == config.calendar_down {
-                self.move_calendar(index, Direction::Down)
-            } else { return };
+    fn get_session(&self, index: usize) -> Session {
+        let selected = self.calendars.get(index).filter(|calendar| calendar.is_selected());
+        Session {
+            month: Some(self.get_start_date().naive_local()),
+            selected: selected.and_then(|_| self.get_selected_date(index)),
+            // Only a view picked with :set, otherwise config.txt decides
+            scroll_mode: self.overrides.iter().find(|(config_var, _)| config_var == "scroll_mode").map(|_| self.config.scroll_mode),
+            search_history: self.search_history.clone(),
+            command_history: self.command_history.clone(),
+        }
+    }
+
+    // Timers and the status bar, after every event
+    fn tick(&mut self, index: &mut usize) {
+        // A key like g waits a moment for the rest of a sequence before running alone
+        if let Some((action, count)) = self.keys.check_timeout(&self.config.keymap, self.get_key_mode()) {
+            self.run_action(action, count, index);
+        }
+        if self.today != Local::today() {
+            self.today = Local::today();
+            self.status.set_message(format!("It is now {}", self.today.format("%A %-d %B %Y")));
+            let format = self.draw_calendars();
             self.terminal.write_format(format);
         }
+        self.update_status(*index);
     }
 
-    fn edit(&mut self, index: &usize) {
-        let lock = self.tx_mut.lock().unwrap(); //Stop other thread
-        let calendar = 
-        match self.calendars.get_mut(*index) {
-            Some(calendar) => calendar,
-            None => return,
+    // Closest of the key sequence timeout, the status message expiring and midnight
+    fn get_next_deadline(&self) -> Instant {
+        // Midnight doesn't exist where summer time starts at it, checking again in an hour gets past the gap
+        let midnight = 
+        match self.today.succ_opt().and_then(|tomorrow| tomorrow.and_hms_opt(0, 0, 0)) {
+            Some(midnight) => (midnight - Local::now()).to_std().unwrap_or_default(),
+            None => Duration::from_secs(60 * 60),
+        };
+        let mut deadline = Instant::now() + midnight;
+        for other in [self.keys.get_deadline(), self.status.get_deadline()].iter().flatten() {
+            deadline = deadline.min(*other);
+        }
+        deadline
+    }
+
+    fn update_status(&mut self, index: usize) {
+        let date = self.get_selected_date(index);
+        self.status.set_context(date, self.notes.iter().count(), self.mode.get_name());
+        // The prompt sits on the same row
+        if self.prompt.is_none() && self.status.needs_draw() {
+            let format = self.status.draw_format();
+            self.terminal.write_format(format);
+        }
+    }
+
+    fn handle_event(&mut self, event: TuiEvent, index: &mut usize) {
+        match event {
+            TuiEvent::Input(Event::Key(key)) => self.handle_key(key, index),
+            TuiEvent::Input(Event::Mouse(mouse)) => self.handle_mouse(mouse, index),
+            TuiEvent::Input(Event::Unsupported(bytes)) => {
+                if let Some((gesture, x, y)) = Gesture::parse(&bytes) {
+                    self.handle_gesture(gesture, Position::new(x, y), index);
+                }
+            },
+            TuiEvent::Resize => {
+                if self.terminal.get_boundaries().is_ok_and(|bounds| bounds != self.bounds) { self.relayout(index); }
+            },
+            TuiEvent::Suspend => self.suspend(index),
+            TuiEvent::Terminate => self.quit = true,
+            TuiEvent::NotesChanged => {
+                // Our own saves touch the file too, there is nothing new to load after those
+                if !self.notes.is_changed_on_disk() { return; }
+                self.notes = Notes::load();
+                for calendar in self.calendars.iter_mut() {
+                    calendar.load_notes(&self.notes);
+                }
+            },
+            TuiEvent::ConfigChanged => {
+                *index = 0;
+                // Before the reset so an unreadable config can replace it with the error
+                self.status.set_message(String::from("Reloaded config"));
+                self.reset(self.get_start_date());
+            },
+        }
+    }
+
+    fn handle_key(&mut self, key: Key, index: &mut usize) {
+        match self.mode {
+            Mode::Search | Mode::GoTo | Mode::Command | Mode::AddEvent => return self.handle_prompt_key(key, index),
+            Mode::SearchResults | Mode::Menu | Mode::Info => return self.handle_list_key(key, index),
+            Mode::Normal | Mode::Visual => (),
+        }
+        if self.awaiting_register {
+            self.awaiting_register = false;
+            if let Key::Char(char) = key { self.register = Some(char); }
+            return;
+        }
+        let mode = self.get_key_mode();
+        if let KeyResult::Action(action, count) = self.keys.push(key, &self.config.keymap, mode) {
+            self.run_action(action, count, index);
+        }
+    }
+
+    fn get_key_mode(&self) -> KeyMode {
+        match self.mode {
+            Mode::Visual => KeyMode::Visual,
+            _ => KeyMode::Normal,
+        }
+    }
+
+    // Count is how many times to repeat the action, like 5d or 3u
+    fn run_action(&mut self, action: Action, count: usize, index: &mut usize) {
+        if let Mode::Visual = self.mode { return self.run_visual_action(action, count, index); }
+        // A register only applies to the action right after it
+        let register = self.register.take();
+        match action {
+            Action::Quit => self.quit = true,
+            Action::Edit => self.edit(index),
+            Action::GoBackTime => self.move_time(Direction::Left, count, index),
+            Action::GoForwardTime => self.move_time(Direction::Right, count, index),
+            Action::GoBackCalendar => self.move_time(Direction::Down, count, index),
+            Action::GoForwardCalendar => self.move_time(Direction::Up, count, index),
+            Action::Today => self.jump_to(Local::today().naive_local(), index),
+            Action::Search => self.open_prompt("/", Mode::Search),
+            Action::GoToDate => self.open_prompt("Go to: ", Mode::GoTo),
+            Action::CommandLine => self.open_prompt(":", Mode::Command),
+            Action::Suspend => self.suspend(index),
+            Action::Help => self.show_info(String::from("Keybindings"), self.config.keymap.get_help()),
+            Action::NextMatch | Action::PreviousMatch => {
+                let mut date = None;
+                if let Some(search) = &mut self.search {
+                    for _ in 0..count {
+                        date = if action == Action::NextMatch { search.next() } else { search.previous() };
+                    }
+                }
+                if let Some(date) = date { self.jump_to(date, index); }
+            },
+            Action::Register => self.awaiting_register = true,
+            Action::Yank | Action::Cut => {
+                if let Some(date) = self.get_selected_date(*index) { self.yank(date, register, action == Action::Cut); }
+            },
+            Action::Paste => {
+                if let Some(date) = self.get_selected_date(*index) { self.paste(date, register, count); }
+            },
+            Action::Undo | Action::Redo => {
+                let mut done = 0;
+                for _ in 0..count {
+                    let changes = if action == Action::Undo { self.undo.undo() } else { self.undo.redo() };
+                    match changes {
+                        Some(changes) => self.write_notes(changes),
+                        None => break,
+                    }
+                    done += 1;
+                }
+                let (verb, end) = if action == Action::Undo { ("Undid", "oldest") } else { ("Redid", "newest") };
+                match done {
+                    0 => self.status.set_error(format!("Already at the {} change", end)),
+                    1 => self.status.set_message(format!("{} 1 change", verb)),
+                    _ => self.status.set_message(format!("{} {} changes", verb, done)),
+                }
+            },
+            Action::Visual => {
+                if let Some(date) = self.get_selected_date(*index) {
+                    self.range = Some((date, date));
+                    self.mode = Mode::Visual;
+                    self.draw_range();
+                }
+            },
+            _ => if let Some(format) = self.move_action(action, count, index) {
+                self.terminal.write_format(format);
+            },
+        }
+    }
+
+    fn move_action(&mut self, action: Action, count: usize, index: &mut usize) -> Option<Formatter> {
+        let (direction, calendar) =
+        match action {
+            Action::Left => (Direction::Left, false),
+            Action::Right => (Direction::Right, false),
+            Action::Up => (Direction::Up, false),
+            Action::Down => (Direction::Down, false),
+            Action::CalendarLeft => (Direction::Left, true),
+            Action::CalendarRight => (Direction::Right, true),
+            Action::CalendarUp => (Direction::Up, true),
+            Action::CalendarDown => (Direction::Down, true),
+            _ => return None,
+        };
+        let mut format = Formatter::new();
+        for _ in 0..count {
+            let view = (self.get_start_date(), self.scroll);
+            let step = if calendar {
+                self.move_calendar(index, direction)
+            } else {
+                self.move_day(index, direction)
+            };
+            // Moving off screen already redrew everything, what came before is out of date
+            if view != (self.get_start_date(), self.scroll) { format = step; } else { format += &step; }
+        }
+        Some(format)
+    }
+
+    // Days and weeks carry on into the next month, bringing it on screen if it isn't
+    fn move_day(&mut self, index: &mut usize, direction: Direction) -> Formatter {
+        let date = 
+        match self.get_selected_date(*index) {
+            Some(date) => date,
+            None => return match self.calendars.get_mut(*index) {
+                Some(calendar) => calendar.move_cursor(&self.config, direction),
+                None => Formatter::new(),
+            },
+        };
+        // Inside the month the grid knows the way, only leaving it needs the dates
+        if let Some(calendar) = self.calendars.get_mut(*index) {
+            if let Some(button) = calendar.get_neighbour(direction) {
+                return calendar.select_button(&self.config, button);
+            }
+        }
+        let days = match direction {
+            Direction::Up => -7,
+            Direction::Down => 7,
+            Direction::Left => -1,
+            Direction::Right => 1,
         };
-        let button = 
-        match calendar.buttons.get_mut(calendar.cursor) {
-            Some(button) => button,
+        let target = 
+        match date.checked_add_signed(chrono::Duration::days(days)) {
+            Some(target) => target,
+            None => return Formatter::new(),
+        };
+        let mut format = Formatter::new();
+        let mut found = self.find_visible(target);
+        if found.is_none() {
+            if self.config.scroll_mode {
+                format += &self.scroll(days.signum(), -(self.scroll as i32), index);
+            } else {
+                let direction = if days > 0 { Direction::Right } else { Direction::Left };
+                let start = self.time_travel(direction, 1);
+                // Already at the last month that can be shown
+                if start == self.get_start_date() { return format; }
+                self.show_months(start);
+            }
+            found = self.find_visible(target);
+        }
+        let (next, button) = 
+        match found {
+            Some(found) => found,
+            None => return format,
+        };
+        if next != *index {
+            if let Some(calendar) = self.calendars.get_mut(*index) {
+                format += &calendar.unselect_button(&self.config);
+            }
+            *index = next;
+        }
+        match self.calendars.get_mut(next) {
+            Some(calendar) => format + &calendar.select_button(&self.config, button),
+            None => format,
+        }
+    }
+
+    // Calendar and button index of a date on screen
+    fn find_visible(&self, date: NaiveDate) -> Option<(usize, usize)> {
+        let date = to_local(date, self.get_start_date());
+        self.calendars.iter().take(self.get_page_size()).enumerate()
+        .find_map(|(index, calendar)| Some((index, calendar.find_date(date)?)))
+    }
+
+    fn run_visual_action(&mut self, action: Action, count: usize, index: &mut usize) {
+        match action {
+            Action::Visual | Action::Quit | Action::Cancel => self.exit_visual(),
+            Action::Edit => self.open_menu(vec![MenuAction::CountDays, MenuAction::AddEvent, MenuAction::Clear], None),
+            _ => if let Some(format) = self.move_action(action, count, index) {
+                self.terminal.write_format(format);
+                if let (Some(date), Some((anchor, _))) = (self.get_selected_date(*index), self.range) {
+                    self.range = Some((anchor, date));
+                    self.draw_range();
+                }
+            },
+        }
+    }
+
+    fn get_range(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let (anchor, end) = self.range?;
+        Some((anchor.min(end), anchor.max(end)))
+    }
+
+    fn draw_range(&mut self) {
+        let range = self.get_range();
+        let mut format = Formatter::new();
+        for calendar in self.calendars.iter_mut() {
+            format += &calendar.set_range(&self.config, range);
+        }
+        self.terminal.write_format(format);
+    }
+
+    fn exit_visual(&mut self) {
+        self.range = None;
+        self.mode = Mode::Normal;
+        self.draw_range();
+    }
+
+    // Dates changed by note actions: the whole range while selecting, otherwise the selected date
+    fn get_target_dates(&self, index: usize) -> Vec<NaiveDate> {
+        match self.get_range() {
+            Some((start, end)) => {
+                let mut dates = Vec::new();
+                let mut date = start;
+                while date <= end {
+                    dates.push(date);
+                    date = date.succ();
+                }
+                dates
+            },
+            None => self.get_selected_date(index).into_iter().collect(),
+        }
+    }
+
+    fn edit(&mut self, index: &usize) {
+        let date = 
+        match self.get_selected_date(*index) {
+            Some(date) => date,
             None => return,
         };
-        let text = &button.internal_text;
-        // TODO save to a file
+        if !self.interactive { return self.status.set_error(String::from("Can't open an editor without a terminal")); }
+        let tx_mut = self.tx_mut.clone();
+        let lock = tx_mut.lock().unwrap(); //Stop other thread
         self.terminal.reset();
-        self.terminal.exit();
-        self.terminal = Terminal::new(); // Drop the raw mode and mouse terminal
+        // Drop the raw mode and mouse terminal
+        if let Err(error) = self.terminal.exit() { return self.fail(error); }
         // I decided to use the lock here to stop the other thread (causes lag to editor input)
-        let text = 
-        match edit::edit(text) {
-            Ok(edit) => edit,
-            Err(_) => String::new(),
-        };
-        drop(lock); // Drop lock and allow use of another self mut refrence
-        button.internal_text = text;
-        drop(button);
-        match calendar.get_text_button() {
-            // This is so dumb. I should not update the calendar date button here
-            Some(_) => {
-                let mut text = String::new();
-                for button in &calendar.buttons {
-                    if let ButtonType::CalanderDate(_) = button.button_data {
-                        text += &button.internal_text;
-                    }
-                }
-                calendar.get_mut_text_button().unwrap().internal_text = text;
-            },
-            None => (),
+        let text = edit::edit(self.notes.get(date));
+        drop(lock);
+        match text {
+            Ok(text) => self.set_note(date, text),
+            Err(error) => self.status.set_error(format!("Could not open the editor: {}", error)),
         }
-        self.terminal = Terminal::new_raw();
-        self.terminal.mouse_terminal();
-        self.terminal.begin();
+        if let Err(error) = self.terminal.begin() { return self.fail(error); }
         let draw = self.draw_calendars();
         self.terminal.write_format(draw);
     }
 
-    fn time_travel(&self, direction: Direction) -> Date<Local> {
-        if self.calendars.is_empty() { return Local::today().with_day(1).unwrap() }
-        let date = self.calendars.first().unwrap().get_start_date();
-        let other_date = self.calendars.last().unwrap().get_start_date();
-        let result = 
-        match direction {
-            Direction::Left => {
-                let other_date = other_date + chrono::Duration::days(5); // Skip a few days into that month.
-                date + date.signed_duration_since(other_date)
-            }, 
-            Direction::Right => {
-                let other_date = other_date + chrono::Duration::days(32); // Skip a month and a bit
-                date + other_date.signed_duration_since(date)
+    // Hands the terminal back to the shell until fg, then draws everything again
+    fn suspend(&mut self, index: &mut usize) {
+        if !self.interactive { return self.status.set_error(String::from("Can't suspend without a terminal")); }
+        self.terminal.reset();
+        if let Err(error) = self.terminal.exit() { return self.fail(error); }
+        events::stop_process();
+        if let Err(error) = self.terminal.begin() { return self.fail(error); }
+        // The size may have changed while stopped
+        self.relayout(index);
+    }
+
+    fn set_note(&mut self, date: NaiveDate, text: String) {
+        self.set_notes(vec![(date, text)]);
+    }
+
+    fn set_notes(&mut self, changes: Vec<(NaiveDate, String)>) {
+        let history = changes.iter()
+            .map(|(date, text)| (*date, self.notes.get(*date).to_string(), text.clone()))
+            .collect();
+        self.undo.push(history);
+        self.write_notes(changes);
+    }
+
+    // Change notes without recording it for undo
+    fn write_notes(&mut self, changes: Vec<(NaiveDate, String)>) {
+        for (date, text) in changes {
+            self.notes.set(date, text);
+        }
+        self.notes.save();
+        for calendar in self.calendars.iter_mut() {
+            calendar.load_notes(&self.notes);
+        }
+    }
+
+    fn append_note(&self, date: NaiveDate, text: &str) -> String {
+        let mut note = self.notes.get(date).to_string();
+        if !note.is_empty() && !note.ends_with('\n') { note.push('\n'); }
+        note + text
+    }
+
+    // Copy a note into a register, the unnamed register '"' always gets a copy
+    fn yank(&mut self, date: NaiveDate, register: Option<char>, cut: bool) {
+        let text = self.notes.get(date).to_string();
+        if text.is_empty() { return self.status.set_error(String::from(if cut { "No note to cut" } else { "No note to copy" })); }
+        if self.config.osc52_clipboard { self.terminal.copy_to_clipboard(&text); }
+        if let Some(register) = register { self.registers.insert(register, text.clone()); }
+        self.registers.insert('"', text);
+        if cut { self.set_note(date, String::new()); }
+        let verb = if cut { "Cut" } else { "Copied" };
+        match register {
+            Some(register) => self.status.set_message(format!("{} note to register {}", verb, register)),
+            None => self.status.set_message(format!("{} note", verb)),
+        }
+    }
+
+    fn paste(&mut self, date: NaiveDate, register: Option<char>, count: usize) {
+        let register = register.unwrap_or('"');
+        match self.registers.get(&register) {
+            Some(text) => {
+                let text = vec![text.as_str(); count].join("\n");
+                let note = self.append_note(date, &text);
+                self.set_note(date, note);
             },
-            Direction::Up => date + chrono::Duration::days(32),
-            Direction::Down => date - chrono::Duration::days(5),
-        }.with_day(1);
+            None => self.status.set_error(format!("Register {} is empty", register)),
+        }
+    }
 
-        match result {
-            Some(value) => value,
-            None => {
-                if matches!(direction, Direction::Left) || matches!(direction, Direction::Down){
-                    chrono::MIN_DATE.with_timezone(&Local)
+    fn get_selected_date(&self, index: usize) -> Option<NaiveDate> {
+        let calendar = self.calendars.get(index)?;
+        match calendar.buttons.get(calendar.cursor)?.button_data {
+            ButtonType::CalanderDate(date) => Some(date.naive_local()),
+            ButtonType::TextButton(_) => None,
+        }
+    }
+
+    fn open_prompt(&mut self, prefix: &str, mode: Mode) {
+        let mut prompt = Prompt::new(prefix, self.bounds, &self.config);
+        match mode {
+            Mode::Search => prompt.set_history(&self.search_history),
+            Mode::Command => {
+                prompt.set_history(&self.command_history);
+                prompt.set_completer(commands::complete);
+            },
+            _ => (),
+        }
+        self.terminal.write_format(prompt.draw_format());
+        self.prompt = Some(prompt);
+        self.mode = mode;
+    }
+
+    fn handle_prompt_key(&mut self, key: Key, index: &mut usize) {
+        let prompt = 
+        match &mut self.prompt {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match prompt.handle_key(key, self.config.keymap.find(KeyMode::Command, &[key])) {
+            PromptAction::Submit(text) => match self.mode {
+                Mode::Search => self.submit_search(text),
+                Mode::GoTo => self.submit_go_to(text, index),
+                Mode::Command => self.submit_command(text, index),
+                Mode::AddEvent => self.submit_event(text, index),
+                _ => (),
+            },
+            PromptAction::Cancel => self.close_overlay(),
+            PromptAction::Changed => {
+                let format = prompt.draw_format();
+                self.terminal.write_format(format);
+            },
+            PromptAction::None => (),
+        }
+    }
+
+    fn prompt_error(&mut self, error: String) {
+        if let Some(prompt) = &mut self.prompt {
+            prompt.set_error(error);
+            let format = prompt.draw_format();
+            self.terminal.write_format(format);
+        }
+    }
+
+    fn submit_search(&mut self, query: String) {
+        if query.is_empty() { return self.close_overlay(); }
+        add_history(&mut self.search_history, &query);
+        let search = Search::new(query, &self.notes);
+        if search.matches.is_empty() { return self.prompt_error(String::from("No matches")); }
+        let title = format!("{} matches for \"{}\"", search.matches.len(), search.query);
+        let mut list = ListBox::new(title, search.get_items(), self.bounds, &self.config);
+        self.prompt = None;
+        let format = self.draw_calendars() + &list.draw_format();
+        self.terminal.write_format(format);
+        self.list = Some(list);
+        self.search = Some(search);
+        self.mode = Mode::SearchResults;
+    }
+
+    fn submit_go_to(&mut self, text: String, index: &mut usize) {
+        match dates::parse_date(&text, Local::today().naive_local()) {
+            Ok(date) => self.jump_to(date, index),
+            Err(error) => self.prompt_error(error),
+        }
+    }
+
+    fn submit_command(&mut self, text: String, index: &mut usize) {
+        if text.trim().is_empty() { return self.close_overlay(); }
+        add_history(&mut self.command_history, &text);
+        let command = 
+        match commands::parse_command(&text) {
+            Ok(command) => command,
+            Err(error) => return self.prompt_error(error),
+        };
+        match command {
+            Command::GoTo(date) => self.submit_go_to(date, index),
+            Command::Export(file) => match self.notes.export(Path::new(&file)) {
+                Ok(_) => {
+                    self.status.set_message(format!("Exported notes to {}", file));
+                    self.close_overlay();
+                },
+                Err(error) => self.prompt_error(format!("Could not export to {}: {}", file, error)),
+            },
+            Command::Import(file) => match self.notes.import(Path::new(&file)) {
+                Ok(count) => {
+                    self.status.set_message(format!("Imported {} notes from {}", count, file));
+                    self.notes.save();
+                    self.reset(self.get_start_date());
+                },
+                Err(error) => self.prompt_error(format!("Could not import {}: {}", file, error)),
+            },
+            Command::Theme(name) => match config::get_theme(&name) {
+                Some(values) => {
+                    for (config_var, value) in values {
+                        self.overrides.push((config_var.to_string(), value.to_string()));
+                    }
+                    self.reset(self.get_start_date());
+                },
+                None => self.prompt_error(format!("Unknown theme \"{}\"", name)),
+            },
+            Command::Set(config_var, value) => {
+                if self.config.set(&config_var, &value) {
+                    // Keep the value when reset reloads the config file
+                    self.overrides.push((config_var, value));
+                    self.reset(self.get_start_date());
+                    self.report_conflicts();
                 } else {
-                    chrono::MAX_DATE.with_timezone(&Local)
+                    self.prompt_error(format!("Can't set {} to \"{}\"", config_var, value));
                 }
             },
+            Command::Quit => self.quit = true,
+        }
+    }
+
+    fn submit_event(&mut self, text: String, index: &mut usize) {
+        if !text.trim().is_empty() {
+            let mut changes = Vec::new();
+            for date in self.get_target_dates(*index) {
+                changes.push((date, self.append_note(date, text.trim())));
+            }
+            self.set_notes(changes);
         }
+        self.exit_visual();
+        self.close_overlay();
+    }
+
+    fn open_menu(&mut self, menu: Vec<MenuAction>, position: Option<Position>) {
+        self.menu = menu;
+        let range = self.range.is_some();
+        let items = self.menu.iter().map(|action| action.get_name(range).to_string()).collect();
+        let title = match self.get_range() {
+            Some((start, end)) => format!("{} - {}", start.format("%b %e"), end.format("%b %e")),
+            None => String::from("Note"),
+        };
+        let mut list = ListBox::new(title, items, self.bounds, &self.config);
+        if let Some(position) = position { list.move_to(position, self.bounds); }
+        self.terminal.write_format(list.draw_format());
+        self.list = Some(list);
+        self.mode = Mode::Menu;
+    }
+
+    fn run_menu_action(&mut self, selected: usize, index: &mut usize) {
+        let action = self.menu.get(selected).copied();
+        let dates = self.get_target_dates(*index);
+        let (action, date) = 
+        match (action, dates.first()) {
+            (Some(action), Some(date)) => (action, *date),
+            _ => return self.close_overlay(),
+        };
+        match action {
+            MenuAction::Edit => {
+                self.close_overlay();
+                self.edit(index);
+            },
+            MenuAction::Clear => {
+                self.set_notes(dates.into_iter().map(|date| (date, String::new())).collect());
+                self.exit_visual();
+                self.close_overlay();
+            },
+            MenuAction::Copy => {
+                self.yank(date, None, false);
+                self.close_overlay();
+            },
+            MenuAction::Paste => {
+                self.paste(date, None, 1);
+                self.close_overlay();
+            },
+            MenuAction::AddEvent => {
+                self.close_overlay();
+                self.open_prompt("Add event: ", Mode::AddEvent);
+            },
+            MenuAction::CountDays => {
+                let workdays = dates.iter().filter(|date| date.weekday().number_from_monday() <= 5).count();
+                let notes = dates.iter().filter(|date| !self.notes.get(**date).is_empty()).count();
+                let items = vec![
+                    format!("Days: {}", dates.len()),
+                    format!("Workdays: {}", workdays),
+                    format!("Weekend days: {}", dates.len() - workdays),
+                    format!("Days with notes: {}", notes),
+                ];
+                let title = self.list.as_ref().map(|list| list.get_title().to_string()).unwrap_or_default();
+                self.show_info(title, items);
+            },
+        }
+    }
+
+    fn activate_list_item(&mut self, selected: usize, index: &mut usize) {
+        match self.mode {
+            Mode::SearchResults => self.jump_to_match(selected, index),
+            Mode::Menu => self.run_menu_action(selected, index),
+            Mode::Info => self.close_overlay(),
+            _ => (),
+        }
+    }
+
+    fn handle_list_key(&mut self, key: Key, index: &mut usize) {
+        let list = 
+        match &mut self.list {
+            Some(list) => list,
+            None => return,
+        };
+        let action = self.config.keymap.find(KeyMode::Normal, &[key]);
+        let format =
+        if action == Some(Action::Up) || key == Key::Up {
+            list.move_selection(Direction::Up)
+        } else if action == Some(Action::Down) || key == Key::Down {
+            list.move_selection(Direction::Down)
+        } else if key == Key::PageUp {
+            list.move_page(Direction::Up)
+        } else if key == Key::PageDown {
+            list.move_page(Direction::Down)
+        } else if action == Some(Action::Edit) || key == Key::Char('\n') {
+            let selected = list.selected;
+            return self.activate_list_item(selected, index);
+        } else if action == Some(Action::Quit) || key == Key::Esc {
+            return self.close_overlay();
+        } else { return };
+        self.terminal.write_format(format);
+    }
+
+    fn show_info(&mut self, title: String, items: Vec<String>) {
+        let mut list = ListBox::new(title, items, self.bounds, &self.config);
+        let format = self.draw_calendars() + &list.draw_format();
+        self.terminal.write_format(format);
+        self.list = Some(list);
+        self.mode = Mode::Info;
+    }
+
+    fn report_conflicts(&mut self) {
+        let conflicts = self.config.keymap.get_conflicts();
+        if !conflicts.is_empty() {
+            self.show_info(String::from("Conflicting bindings"), conflicts);
+        }
+    }
+
+    fn jump_to_match(&mut self, selected: usize, index: &mut usize) {
+        let date = self.search.as_mut().and_then(|search| search.select(selected));
+        match date {
+            Some(date) => self.jump_to(date, index),
+            None => self.close_overlay(),
+        }
+    }
+
+    // Rebuild the view around the month of date and put the cursor on it
+    fn jump_to(&mut self, date: NaiveDate, index: &mut usize) {
+        // Days whose midnight is skipped for summer time still start, just later
+        let month = date.with_day(1).and_then(|month| Local.from_local_date(&month).earliest());
+        let (date, month) = 
+        match (Local.from_local_date(&date).earliest(), month) {
+            (Some(date), Some(month)) => (date, month),
+            _ => {
+                self.close_overlay();
+                return self.status.set_error(format!("Can't show {}", date.format("%-d %B %Y")));
+            },
+        };
+        self.scroll = 0;
+        self.show_months(month);
+        *index = 0;
+        let calendar = 
+        match self.calendars.get_mut(*index) {
+            Some(calendar) => calendar,
+            None => return,
+        };
+        if let Some(button_index) = calendar.find_date(date) {
+            let format = calendar.select_button(&self.config, button_index);
+            self.terminal.write_format(format);
+        }
+    }
+
+    fn close_overlay(&mut self) {
+        self.prompt = None;
+        self.list = None;
+        self.mode = if self.range.is_some() { Mode::Visual } else { Mode::Normal };
+        let format = self.draw_calendars();
+        self.terminal.write_format(format);
+    }
+
+    fn get_start_date(&self) -> Date<Local> {
+        self.start
+    }
+
+    fn find_month(&self, month: Date<Local>) -> Option<usize> {
+        self.calendars.iter().take(self.get_page_size()).position(|calendar| calendar.get_start_date() == month)
+    }
+
+    // Left and right move by a whole screen of calendars, up and down by one month
+    fn time_travel(&self, direction: Direction, count: usize) -> Date<Local> {
+        let months = match direction {
+            Direction::Left | Direction::Right => self.get_page_size(),
+            Direction::Up | Direction::Down => 1,
+        };
+        let months = (months as i64).saturating_mul(count as i64);
+        let months = match direction {
+            Direction::Left | Direction::Down => -months,
+            Direction::Right | Direction::Up => months,
+        };
+        add_months(self.get_start_date(), months)
+    }
+
+    fn get_page_size(&self) -> usize {
+        self.layout.map_or(1, |layout| layout.get_count())
+    }
+
+    // Scroll mode moves the same distance but by rows of months, keeping the cursor where it was
+    fn move_time(&mut self, direction: Direction, count: usize, index: &mut usize) {
+        if !self.config.scroll_mode { return self.show_months(self.time_travel(direction, count)); }
+        let rows = match direction {
+            Direction::Left | Direction::Right => self.layout.map_or(1, |layout| layout.rows),
+            Direction::Up | Direction::Down => 1,
+        };
+        let rows = (rows as i64).saturating_mul(count as i64);
+        let rows = match direction {
+            Direction::Left | Direction::Down => -rows,
+            Direction::Right | Direction::Up => rows,
+        };
+        let format = self.scroll(rows, 0, index);
+        self.terminal.write_format(format);
+    }
+
+    // Moves the months like an infinite list, by whole rows and then lines within a row.
+    // The cursor stays on the same date while its month is on screen
+    fn scroll(&mut self, rows: i64, lines: i32, index: &mut usize) -> Formatter {
+        let layout = 
+        match self.layout {
+            Some(layout) => layout,
+            None => return Formatter::new(),
+        };
+        let step = layout.get_step_y() as i32;
+        let (rows, lines) = 
+        if step == 0 { (rows.saturating_add(lines.signum() as i64), 0) }
+        else {
+            let lines = self.scroll as i32 + lines;
+            (rows.saturating_add(lines.div_euclid(step) as i64), lines.rem_euclid(step))
+        };
+        let selected = self.calendars.get(*index).map(|calendar| (calendar.get_start_date(), calendar.cursor, calendar.is_selected()));
+        self.scroll = lines as u16;
+        let columns = layout.columns;
+        self.show_months(add_months(self.get_start_date(), rows.saturating_mul(columns as i64)));
+        let (month, cursor, was_selected) = 
+        match selected {
+            Some(selected) => selected,
+            None => return Formatter::new(),
+        };
+        let count = layout.get_count();
+        *index = match self.find_month(month) {
+            Some(found) => found,
+            // The month went off screen so take its place in the row nearest to it
+            None if rows > 0 => *index % columns,
+            None => count - columns + *index % columns,
+        };
+        let calendar = 
+        match self.calendars.get_mut(*index) {
+            Some(calendar) => calendar,
+            None => return Formatter::new(),
+        };
+        if !was_selected { return Formatter::new(); }
+        let cursor = cursor.min(calendar.buttons.len().saturating_sub(1));
+        calendar.select_button(&self.config, cursor)
+    }
+
+    // Mouse position in the coordinates the calendars were laid out in, None on the status bar
+    fn to_grid(&self, position: Position) -> Option<Position> {
+        if position.get_y() >= self.bounds.get_y() { return None; }
+        Some(position.offset(0, self.scroll as i32))
     }
 
     fn handle_mouse(&mut self, mouse: MouseEvent, index: &mut usize) {
-        if let MouseEvent::Press(mouse, x, y) = mouse {
-            if !matches!(mouse, MouseButton::Left) { return; }
-            let mut calendar_change = false;
-            let mut future_index = self.calendars.len() + 1;
-            let mouse_pos = Position::new(x, y);
-            let mut format = Formatter::new();
-            for (calendar_index, calendar) in self.calendars.iter_mut().enumerate() {
-                if !calendar.is_hovered(mouse_pos) { continue }
-                for (i, button) in calendar.buttons.iter_mut().enumerate() {
-                    if !button.is_hovered(mouse_pos) { continue }
-                    if *index != calendar_index {
-                        calendar_change = true;
-                        future_index = calendar_index;
-                    }
-                    format += &calendar.select_button(&mut self.config, i);
-                    break;
-                }
+        match self.mode {
+            Mode::SearchResults | Mode::Menu | Mode::Info => return self.handle_list_mouse(mouse, index),
+            Mode::Normal | Mode::Visual => (),
+            _ => return,
+        }
+        match mouse {
+            MouseEvent::Press(button, x, y) => self.handle_press(button, Position::new(x, y), index),
+            MouseEvent::Hold(x, y) => self.handle_drag(Position::new(x, y), index),
+            MouseEvent::Release(_, _) => self.drag_start = None,
+        }
+    }
+
+    // Runs the action bound to the gesture, clicks select the date under the mouse first
+    fn handle_gesture(&mut self, gesture: Gesture, mouse_pos: Position, index: &mut usize) -> bool {
+        match self.mode {
+            Mode::Normal | Mode::Visual => (),
+            _ => return false,
+        }
+        let action = 
+        match self.config.keymap.find_mouse(self.get_key_mode(), gesture) {
+            Some(action) => action,
+            None => return false,
+        };
+        if gesture.is_click() && self.click(mouse_pos, index).is_none() { return true; }
+        self.keys.clear();
+        if !gesture.is_click() && self.config.scroll_mode && self.config.smooth_scroll {
+            // The wheel moves the months a few lines at a time instead of a whole row
+            let lines = self.config.scroll_speed as i32;
+            let lines = match action {
+                Action::GoBackTime | Action::GoBackCalendar => Some(-lines),
+                Action::GoForwardTime | Action::GoForwardCalendar => Some(lines),
+                _ => None,
+            };
+            if let Some(lines) = lines {
+                let format = self.scroll(0, lines, index);
+                self.terminal.write_format(format);
+                return true;
+            }
+        }
+        self.run_action(action, 1, index);
+        true
+    }
+
+    fn handle_press(&mut self, mouse: MouseButton, mouse_pos: Position, index: &mut usize) {
+        if self.handle_gesture(Gesture::new(mouse), mouse_pos, index) { return; }
+        match mouse {
+            MouseButton::Left => if self.range.is_some() { self.exit_visual(); },
+            MouseButton::Right => (),
+            _ => return,
+        }
+        let (calendar_index, button_index) = 
+        match self.click(mouse_pos, index) {
+            Some(clicked) => clicked,
+            None => return,
+        };
+        if let MouseButton::Right = mouse {
+            if self.range.is_some() {
+                self.open_menu(vec![MenuAction::CountDays, MenuAction::AddEvent, MenuAction::Clear], Some(mouse_pos));
+            } else if self.get_selected_date(*index).is_some() {
+                let mut menu = vec![MenuAction::Edit, MenuAction::Clear, MenuAction::Copy, MenuAction::AddEvent];
+                if self.registers.contains_key(&'"') { menu.insert(3, MenuAction::Paste); }
+                self.open_menu(menu, Some(mouse_pos));
+            }
+            return;
+        }
+        self.drag_start = self.get_selected_date(*index);
+        let double_click = Duration::from_millis(self.config.double_click_ms as u64);
+        match self.last_click {
+            Some((time, last_calendar, last_button)) if time.elapsed() <= double_click
+            && last_calendar == calendar_index && last_button == button_index => {
+                self.last_click = None;
+                self.edit(index);
+            },
+            _ => self.last_click = Some((Instant::now(), calendar_index, button_index)),
+        }
+    }
+
+    // Dragging from a date selects every date up to the one under the mouse
+    fn handle_drag(&mut self, mouse_pos: Position, index: &mut usize) {
+        let start = 
+        match self.drag_start {
+            Some(start) => start,
+            None => return,
+        };
+        if self.click(mouse_pos, index).is_none() { return; }
+        let date = 
+        match self.get_selected_date(*index) {
+            Some(date) => date,
+            None => return,
+        };
+        if self.range.is_none() && date == start { return; }
+        self.range = Some((start, date));
+        self.mode = Mode::Visual;
+        self.draw_range();
+    }
+
+    // Select the button under the mouse, returns the calendar and button index
+    fn click(&mut self, mouse_pos: Position, index: &mut usize) -> Option<(usize, usize)> {
+        let mouse_pos = self.to_grid(mouse_pos)?;
+        let mut calendar_change = false;
+        let mut future_index = self.calendars.len() + 1;
+        let mut clicked = None;
+        let mut format = Formatter::new();
+        for (calendar_index, calendar) in self.calendars.iter_mut().enumerate() {
+            let i = 
+            match calendar.find_button(mouse_pos) {
+                Some(i) => i,
+                None => continue,
+            };
+            if *index != calendar_index {
+                calendar_change = true;
+                future_index = calendar_index;
             }
+            format += &calendar.select_button(&self.config, i);
+            clicked = Some((calendar_index, i));
+            break;
+        }
 
-            if calendar_change { 
-                let last_calendar = self.calendars.get_mut(*index).unwrap();
+        if calendar_change { 
+            if let Some(last_calendar) = self.calendars.get_mut(*index) {
                 if self.config.unselect_change_calendar_cursor || self.config.change_calendar_reset_cursor {
-                    format += &last_calendar.unselect_button(&mut self.config);
+                    format += &last_calendar.unselect_button(&self.config);
                 }
                 if self.config.change_calendar_reset_cursor { last_calendar.cursor = 0; }  
-                *index = future_index;  
             }
-            self.terminal.write_format(format);
+            *index = future_index;  
+        }
+        self.terminal.write_format(format);
+        clicked
+    }
+
+    fn handle_list_mouse(&mut self, mouse: MouseEvent, index: &mut usize) {
+        let direction = match mouse {
+            MouseEvent::Press(MouseButton::WheelUp, _, _) => Some(Direction::Up),
+            MouseEvent::Press(MouseButton::WheelDown, _, _) => Some(Direction::Down),
+            _ => None,
+        };
+        if let (Some(direction), Some(list)) = (direction, &mut self.list) {
+            let format = list.move_selection(direction);
+            return self.terminal.write_format(format);
+        }
+        if let MouseEvent::Press(MouseButton::Left, x, y) = mouse {
+            let position = Position::new(x, y);
+            let list = 
+            match &self.list {
+                Some(list) => list,
+                None => return,
+            };
+            if !list.is_hovered(position) { return self.close_overlay(); }
+            if let Some(selected) = list.get_hovered_item(position) { self.activate_list_item(selected, index); }
         }
     }
 
@@ -232,83 +1170,104 @@ impl Tui {
         create_box(&Position::new_origin(), &Position::new(self.bounds.get_x(), self.bounds.get_y()), &self.config.bg_color)
     }
 
-    fn get_columns() -> usize {
-        let mut position = Position::new_origin();
-        let mut count:  usize = 0;
-        loop {
-            if !position.set_x(position.get_x() + 23) {
-                break;
-            } else { count += 1 } 
-            
-            if !position.set_x(position.get_x() + 1) {
-                break;
-            }
+    fn draw_too_small(&self) -> Formatter {
+        let lines = [
+            String::from("Terminal too small"),
+            format!("Need {}x{}", layout::CALENDAR_WIDTH, layout::COMPACT_HEIGHT + 1),
+        ];
+        let mut format = Formatter::new()
+        .bg_color(&self.config.bg_color)
+        .fg_color(&self.config.month_text_color);
+        let top = (self.bounds.get_y().saturating_sub(lines.len() as u16)) / 2 + 1;
+        for (row, line) in lines.iter().enumerate() {
+            let line: String = line.chars().take(self.bounds.get_x() as usize).collect();
+            let x = (self.bounds.get_x() - line.chars().count() as u16) / 2 + 1;
+            format = format.go_to(Position::new(x, top + row as u16)).text(line);
         }
-        count
+        format
     }
 
-    fn get_rows() -> usize {
-        let mut position = Position::new_origin();
-        let mut count: usize = 0;
-        loop {
-            if !position.set_y(position.get_y() + 13) {
-                break;
-            } else { count += 1; }
-            
-            if !position.set_y(position.get_y() + 1) {
-                break;
-            }
-        }
-        count
+    fn get_columns(&self) -> usize {
+        self.layout.map_or(1, |layout| layout.columns)
     }
 
     pub fn create_calendars(&mut self, date: Date<Local>) {
-        let columns = Tui::get_columns();
-        let rows = Tui::get_rows();
-        let threads = self.config.max_threads;
-        let threads = 
-        if rows > threads || columns > threads { threads } 
-        else if rows >= columns { rows }
-        else { columns };
-        // Put all this here because they all relate and need to be in sync
-        let mutex = Arc::new(Mutex::new((date, Position::new_origin(), 0)));
-        let max = rows * columns;
+        // Calendars still on screen after the move get reused instead of built again
+        for calendar in self.calendars.drain(..) {
+            self.months.put(calendar);
+        }
+        self.start = date;
+        self.layout = Layout::new(self.bounds);
+        let layout = 
+        match self.layout {
+            Some(layout) => layout,
+            None => return,
+        };
+        // Scrolling by lines shows part of the row under the screen
+        let scrolling = self.config.scroll_mode && layout.get_step_y() > 0;
+        let max = layout.get_count() + if scrolling { layout.columns } else { 0 };
         let mut vec = vec![Calendar::dummy(); max]; //fill up space
+        let mut missing = Vec::new();
+        let mut next = Some(date);
+        for (index, calendar) in vec.iter_mut().enumerate() {
+            // Months after the last one chrono can lay out are left empty
+            let month = 
+            match next {
+                Some(month) => month,
+                None => break,
+            };
+            next = Month::containing(month.naive_local(), self.config.week_start).and_then(|month| month.next())
+            .map(|next| to_local(next.get_first_day(), month));
+            match self.months.take(month) {
+                Some(mut cached) => {
+                    cached.move_to(layout.get_position(index));
+                    cached.clear_selection();
+                    *calendar = cached;
+                },
+                None => missing.push((month, index)),
+            }
+        }
+        self.months.trim(date, max * 2);
+
+        let count = missing.len();
+        let threads = self.config.max_threads.min(count);
+        // Put all this here because they all relate and need to be in sync
+        let mutex = Arc::new(Mutex::new(missing));
         let (tx, rx) = channel();
         let handles: Vec<_> = (0..threads).map(|_| {
-            let config = self.config; // Auto cloned config
+            let config = self.config.clone();
             let mutex = mutex.clone();
             let tx = tx.clone();
             thread::spawn(move || {
                 loop {
-                    let date;
-                    let position;
-                    let index;
-                    { // Put in a new scope to force the lock drop and unlock for other threads
-                        let mut lock  = mutex.lock().unwrap();
-                        if lock.2 >= max { break; }
-                        date = lock.0.clone();
-                        position = lock.1.clone();
-                        index = lock.2.clone();
-                        lock.0 = (date + chrono::Duration::days(32)).with_day(1).unwrap();
-                        lock.1.set_x(position.get_x() + 24);
-                        lock.2 += 1;
-                        if lock.2 % Tui::get_columns() == 0 { lock.1.set(1, position.get_y() + 14); }
-                    }
-                    tx.send((Calendar::new(date, position, &config), index)).unwrap();
+                    // Pop in its own statement so the lock drops before building
+                    let next = mutex.lock().unwrap().pop();
+                    let (date, index) = 
+                    match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let calendar = Calendar::new(date, layout.get_position(index), layout.compact, &config);
+                    if tx.send((calendar, index)).is_err() { break; }
                 }
             })
         }).collect();
 
         for handle in handles {
-            handle.join().unwrap();
+            if handle.join().is_err() { self.fail(Error::Worker); }
         }
 
-        for _ in 0..max {
-            let (calendar, index) = rx.recv().unwrap();
-            *vec.get_mut(index).unwrap() = calendar;
+        for (calendar, index) in rx.try_iter() {
+            if let Some(slot) = vec.get_mut(index) { *slot = calendar; }
         }
 
+        let range = self.get_range();
+        let last_row = if scrolling { self.bounds.get_y() - 1 } else { u16::MAX };
+        for calendar in vec.iter_mut() {
+            calendar.set_view(self.scroll, last_row);
+            calendar.load_notes(&self.notes);
+            calendar.set_range(&self.config, range);
+        }
         self.calendars = vec;
     }
 
@@ -329,71 +1288,169 @@ impl Tui {
                             None => break,
                         };
                     }
-                    tx.send(calendar.draw_format()).unwrap();
+                    if tx.send(calendar.draw_format()).is_err() { break; }
                 }
             })
         }).collect();
 
         // Wait for all threads to finish
         for handle in handles {
-            handle.join().unwrap();
+            if handle.join().is_err() { self.fail(Error::Worker); }
         }
 
         // Collect thread messages
         let mut format = Formatter::new();
-        for _ in 0..self.calendars.len() {
-            format += &rx.recv().unwrap();
+        for calendar in rx.try_iter() {
+            format += &calendar;
         }
         
-        self.draw_background() + &format
+        let mut format = self.draw_background() + &format;
+        if self.layout.is_none() { format += &self.draw_too_small(); }
+        if self.prompt.is_none() { format += &self.status.draw_format(); }
+        format
     }
 
     fn move_calendar(&mut self, index: &mut usize, direction: Direction) -> Formatter {
-        let change;
         let mut format = Formatter::new();
-        match direction {
-            Direction::Up | Direction::Down => {
-                let x = Tui::get_columns();
-                if let Direction::Up = direction {
-                    if *index == 0 || *index < x { return format; }
-                } else if *index + x >= self.calendars.len() { return format; }
-                change = x;
-            },
-            _ => {
-                if let Direction::Left = direction {
-                    if *index == 0 { return format; }
-                } else if *index + 1 >= self.calendars.len() { return format; }
-                change = 1;
-            },
+        let change = match direction {
+            Direction::Up | Direction::Down => self.get_columns() as i64,
+            Direction::Left | Direction::Right => 1,
+        };
+        let change = if matches!(direction, Direction::Up | Direction::Left) { -change } else { change };
+        let count = self.get_page_size() as i64;
+        let mut next = *index as i64 + change;
+        if next < 0 || next >= count {
+            if !self.config.scroll_mode { return format; }
+            // Bring the row with the month in first, the cursor's month moves with it
+            let month = 
+            match self.calendars.get(*index) {
+                Some(calendar) => add_months(calendar.get_start_date(), change),
+                None => return format,
+            };
+            format += &self.scroll(change.signum(), -(self.scroll as i32), index);
+            next = 
+            match self.find_month(month) {
+                Some(next) => next as i64,
+                None => return format,
+            };
         }
-        let calendar = self.calendars.get_mut(*index).unwrap();
+        let calendar = 
+        match self.calendars.get_mut(*index) {
+            Some(calendar) => calendar,
+            None => return format,
+        };
         if self.config.unselect_change_calendar_cursor || self.config.change_calendar_reset_cursor {
-            format += &calendar.unselect_button(&mut self.config);
+            format += &calendar.unselect_button(&self.config);
         }
-        if matches!(direction, Direction::Down) || matches!(direction, Direction::Right) { 
-            *index += change; 
-        } else { *index -= change; }
         if self.config.change_calendar_reset_cursor { calendar.cursor = 0; }
-        let calendar = self.calendars.get_mut(*index).unwrap();
-        format + &calendar.select_button(&mut self.config, calendar.cursor)
+        *index = next as usize;
+        match self.calendars.get_mut(*index) {
+            Some(calendar) => format + &calendar.select_button(&self.config, calendar.cursor),
+            None => format,
+        }
     }
 
     fn reset(&mut self, date: Date<Local>) {
-        self.terminal.reset();
-        self.bounds = Terminal::get_boundaries();
-        self.config = Config::get_config();
+        self.prompt = None;
+        self.list = None;
+        self.mode = if self.range.is_some() { Mode::Visual } else { Mode::Normal };
+        // Keep going with what was there before when the size or config can't be read
+        if let Ok(bounds) = self.terminal.get_boundaries() { self.bounds = bounds; }
+        self.terminal.resize(self.bounds);
+        match Config::get_config() {
+            Ok(config) => self.config = config,
+            Err(error) => self.status.set_error(error.to_string()),
+        }
+        for (config_var, value) in self.overrides.iter() {
+            self.config.set(config_var, value);
+        }
+        self.undo.set_limit(self.config.undo_limit);
+        self.status.replace(StatusBar::new(self.bounds, &self.config));
         self.calendars.clear();
+        self.months.clear();
+        self.scroll = 0;
+        self.init(date);
+    }
+
+    // Lay the same months out for the new size, keeping the selected calendar and date on screen
+    fn relayout(&mut self, index: &mut usize) {
+        let selected = self.calendars.get(*index).map(|calendar| (calendar.get_start_date(), calendar.cursor, calendar.is_selected()));
+        self.reset(self.get_start_date());
+        let (month, cursor, was_selected) = 
+        match selected {
+            Some(selected) => selected,
+            None => return *index = 0,
+        };
+        *index = match self.find_month(month) {
+            Some(found) => found,
+            // Fewer months fit now so start from the selected one
+            None => {
+                self.show_months(month);
+                0
+            },
+        };
+        let calendar = 
+        match self.calendars.get_mut(*index) {
+            Some(calendar) => calendar,
+            None => return,
+        };
+        if !was_selected { return; }
+        let cursor = cursor.min(calendar.buttons.len().saturating_sub(1));
+        let format = calendar.select_button(&self.config, cursor);
+        self.terminal.write_format(format);
+    }
+
+    // Moving through time keeps the config and screen, only months that weren't
+    // around before get built and the renderer only writes cells that changed
+    fn show_months(&mut self, date: Date<Local>) {
+        self.prompt = None;
+        self.list = None;
+        self.mode = if self.range.is_some() { Mode::Visual } else { Mode::Normal };
         self.init(date);
     }
 }
 
+// The first of this month
+pub fn get_this_month() -> Date<Local> {
+    let today = Local::today();
+    let first = today.naive_local().with_day(1).unwrap_or_else(|| today.naive_local());
+    to_local(first, today)
+}
+
+// Where summer time skips midnight the day starts later, or keeps the offset of a date near it
+// when chrono has no time for the day at all
+pub fn to_local(date: NaiveDate, near: Date<Local>) -> Date<Local> {
+    match Local.from_local_date(&date).earliest() {
+        Some(date) => date,
+        None => Date::from_utc(date, *near.offset()),
+    }
+}
+
+fn add_months(date: Date<Local>, months: i64) -> Date<Local> {
+    let month = (date.year() as i64 * 12 + date.month0() as i64).saturating_add(months);
+    let first = i32::try_from(month.div_euclid(12)).ok()
+    .and_then(|year| NaiveDate::from_ymd_opt(year, month.rem_euclid(12) as u32 + 1, 1));
+    // Past either end stop at the first or last month that can be laid out, the week start doesn't matter for that
+    let first = match first.and_then(|first| Month::containing(first, Weekday::Mon)) {
+        Some(month) => month.get_first_day(),
+        None if months < 0 => Month::earliest(Weekday::Mon).get_first_day(),
+        None => Month::latest(Weekday::Mon).get_first_day(),
+    };
+    to_local(first, date)
+}
+
+fn add_history(history: &mut Vec<String>, text: &str) {
+    if history.last().map(|last| last.as_str()) != Some(text) {
+        history.push(text.to_string());
+    }
+}
+
 pub trait Widget {
     fn is_hovered(&self, position: Position) -> bool {
         position.get_x() >= self.get_start().get_x() && position.get_y() >= self.get_start().get_y()
         && position.get_x() <= self.get_end().get_x() && position.get_y() <= self.get_end().get_y()
     }
     fn draw_format(&mut self) -> Formatter;
-    fn action(&mut self);
     fn get_start(&self) -> Position;
     fn get_end(&self) -> Position;
 }
@@ -422,10 +1479,6 @@ impl Widget for Button {
         }
     }
 
-    fn action(&mut self) {
-        todo!("Make button actions for date buttons and text buttons. Date buttons allow you to insert text, while text button give overview")
-    }
-
     fn get_start(&self) -> Position {
         self.start_position
     }
@@ -436,7 +1489,7 @@ impl Widget for Button {
 }
 
 impl Button {
-    fn draw_text_button(&self, text: &String) -> Formatter {
+    fn draw_text_button(&self, text: &str) -> Formatter {
         let mut center_x: u16 = (self.end_position.get_x() + self.start_position.get_x()) / 2;
         let length: u16 = text.chars().count() as u16 / 2;
         if center_x >= length { center_x -= length; }
@@ -446,7 +1499,7 @@ impl Button {
         .go_to(Position::new(center_x, center_y))
         .bg_color(&self.bg_color)
         .fg_color(&self.fg_color)
-        .text(text.clone())
+        .text(text.to_string())
     }
 
     fn draw_calendar_date(&self, date: &Date<Local>) -> Formatter {
@@ -458,9 +1511,10 @@ impl Button {
     }
 }
 
+#[derive(Clone)]
 pub struct TextBox {
     text: String,
-    pub position: Position,
+    position: Position,
     color: AnsiValue,
 }
 
@@ -468,7 +1522,75 @@ impl TextBox {
     pub fn new(text: String, position: Position, color: AnsiValue) -> Self{
         TextBox { text, position, color }
     }
+    pub fn set_position(&mut self, position: Position) {
+        self.position = position;
+    }
     pub fn draw_format(&self) -> Formatter {
         Formatter::new().go_to(self.position).bg_color(&self.color).text(self.text.clone())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use termion::event::{Event, Key};
+
+    use crate::{config::Config, headless::VirtualBackend, notes::Notes, position::Position};
+
+    use super::Tui;
+
+    // Plays keys on a virtual terminal starting at March 2027 and returns the screen
+    fn snapshot(width: u16, height: u16, config: Config, keys: &str) -> Vec<String> {
+        let backend = VirtualBackend::new(Position::new(width, height));
+        let screen = backend.clone();
+        let mut tui = Tui::with_backend(Box::new(backend), config, Notes::new()).unwrap();
+        let events = keys.chars().map(|char| Event::Key(Key::Char(char)));
+        tui.run_script(Local.ymd(2027, 3, 1), events).unwrap();
+        screen.get_lines()
+    }
+
+    fn status(lines: &[String]) -> &str {
+        lines.last().map_or("", |line| line.as_str())
+    }
+
+    #[test]
+    fn draws_a_row_of_months() {
+        let lines = snapshot(80, 24, Config::default(), "");
+        assert_eq!(lines.len(), 24);
+        assert!(lines.iter().any(|line| line.contains("March 2027") && line.contains("April 2027") && line.contains("May 2027")));
+        assert!(lines.iter().any(|line| line.trim_start().starts_with("SunMonTueWedThuFriSat")));
+        assert!(lines.iter().any(|line| line.trim_start().starts_with("1  2  3  4  5  6")));
+        assert_eq!(status(&lines), " NORMAL | 0 notes");
+    }
+
+    #[test]
+    fn moves_the_cursor_across_months() {
+        let lines = snapshot(80, 24, Config::default(), "s");
+        assert!(status(&lines).contains("Monday 1 March 2027"));
+        let lines = snapshot(80, 24, Config::default(), "ssss");
+        assert!(status(&lines).contains("Monday 22 March 2027"));
+        let lines = snapshot(80, 24, Config::default(), "ssssssd");
+        assert!(status(&lines).contains("Tuesday 6 April 2027"));
+    }
+
+    #[test]
+    fn follows_the_week_start() {
+        let mut config = Config::default();
+        assert!(config.set("week_start", "mon"));
+        let lines = snapshot(80, 24, config, "");
+        assert!(lines.iter().any(|line| line.trim_start().starts_with("MonTueWedThuFriSatSun")));
+        // March 2027 starts on a Monday so its days begin in the first column
+        assert!(lines.iter().any(|line| line.trim_start().starts_with("1  2  3  4  5  6  7")));
+    }
+
+    #[test]
+    fn says_when_the_terminal_is_too_small() {
+        let lines = snapshot(20, 10, Config::default(), "");
+        assert!(lines.iter().any(|line| line.contains("Terminal too small")));
+    }
+
+    #[test]
+    fn rejects_years_out_of_range() {
+        let lines = snapshot(80, 24, Config::default(), "gt262143-12-01\n");
+        assert!(status(&lines).contains("Year 262143 is out of range"));
+    }
+}
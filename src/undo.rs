@@ -0,0 +1,106 @@
+use chrono::NaiveDate;
+
+// One user action, every date it touched with the note before and after
+struct Change {
+    notes: Vec<(NaiveDate, String, String)>,
+}
+
+pub struct UndoHistory {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    limit: usize,
+}
+
+impl UndoHistory {
+    pub fn new(limit: usize) -> Self {
+        UndoHistory { undo: Vec::new(), redo: Vec::new(), limit }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    pub fn push(&mut self, notes: Vec<(NaiveDate, String, String)>) {
+        let notes: Vec<_> = notes.into_iter().filter(|(_, before, after)| before != after).collect();
+        if notes.is_empty() { return; }
+        self.undo.push(Change { notes });
+        self.redo.clear();
+        self.trim();
+    }
+
+    // Returns the notes to write back to undo the last change
+    pub fn undo(&mut self) -> Option<Vec<(NaiveDate, String)>> {
+        let change = self.undo.pop()?;
+        let notes = change.notes.iter().map(|(date, before, _)| (*date, before.clone())).collect();
+        self.redo.push(change);
+        Some(notes)
+    }
+
+    pub fn redo(&mut self) -> Option<Vec<(NaiveDate, String)>> {
+        let change = self.redo.pop()?;
+        let notes = change.notes.iter().map(|(date, _, after)| (*date, after.clone())).collect();
+        self.undo.push(change);
+        Some(notes)
+    }
+
+    fn trim(&mut self) {
+        if self.undo.len() > self.limit {
+            let extra = self.undo.len() - self.limit;
+            self.undo.drain(..extra);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::UndoHistory;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2027, 3, day)
+    }
+
+    fn change(day_of_month: u32, before: &str, after: &str) -> Vec<(NaiveDate, String, String)> {
+        vec![(day(day_of_month), before.to_string(), after.to_string())]
+    }
+
+    #[test]
+    fn undoes_and_redoes_in_order() {
+        let mut history = UndoHistory::new(10);
+        history.push(change(1, "", "a"));
+        history.push(change(2, "", "b"));
+        assert_eq!(history.undo(), Some(vec![(day(2), String::new())]));
+        assert_eq!(history.undo(), Some(vec![(day(1), String::new())]));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(vec![(day(1), String::from("a"))]));
+        assert_eq!(history.redo(), Some(vec![(day(2), String::from("b"))]));
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn new_change_after_undo_drops_redo() {
+        let mut history = UndoHistory::new(10);
+        history.push(change(1, "", "a"));
+        history.push(change(2, "", "b"));
+        history.undo();
+        history.push(change(3, "", "c"));
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(vec![(day(3), String::new())]));
+        assert_eq!(history.undo(), Some(vec![(day(1), String::new())]));
+    }
+
+    #[test]
+    fn skips_empty_changes_and_keeps_the_limit() {
+        let mut history = UndoHistory::new(2);
+        history.push(change(1, "same", "same"));
+        assert_eq!(history.undo(), None);
+        history.push(change(1, "", "a"));
+        history.push(change(2, "", "b"));
+        history.push(change(3, "", "c"));
+        assert!(history.undo().is_some());
+        assert!(history.undo().is_some());
+        assert_eq!(history.undo(), None);
+    }
+}